@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::builtins;
+use crate::engine::{text_of, Delimiters, Engine, Options};
+use crate::error::{Sink, Stream};
+use crate::input::Input;
+use crate::regex::Regex;
+
+/// Chainable configuration for an [`Engine`].
+///
+/// The defaults match the `lc-m4` command line: GNU mode, a nesting limit of
+/// 1024, the standard `` ` ``/`'` quotes and `#` comments, output to stdout
+/// and debug output and diagnostics to stderr.
+///
+/// ```
+/// use lc_m4::EngineBuilder;
+///
+/// let mut out = Vec::new();
+/// let mut engine = EngineBuilder::new()
+///     .traditional()
+///     .quotes("[", "]")
+///     .comments("%", "\n")
+///     .output(&mut out)
+///     .build()
+///     .unwrap();
+/// engine.process(&mut "hello % world\n".as_bytes()).unwrap();
+/// drop(engine);
+/// assert_eq!(out, b"hello % world\n");
+/// ```
+pub struct EngineBuilder<'a> {
+    options: Options,
+    delimiters: Delimiters,
+    output: Option<Box<dyn Write + 'a>>,
+    debug_output: Option<Box<dyn Write + 'a>>,
+    diagnostics: Option<Box<dyn Write + 'a>>,
+}
+
+/// Reasons an [`EngineBuilder`] can refuse to build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// The word regexp doesn't compile.
+    BadWordRegexp {
+        regexp: String,
+        reason: &'static str,
+    },
+    /// The regexp for `--warn-macro-sequence` doesn't compile.
+    BadMacroSequence {
+        regexp: String,
+        reason: &'static str,
+    },
+    /// An explicit shell was requested, but safer mode forbids running one.
+    ShellInSaferMode,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadWordRegexp { regexp, reason } | Self::BadMacroSequence { regexp, reason } => {
+                write!(f, "bad regular expression `{regexp}': {reason}")
+            }
+            Self::ShellInSaferMode => {
+                f.write_str("a shell was configured, but safer mode disables shell commands")
+            }
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// Compiles a word regexp; unlike other regexps it can't be empty.
+fn compile_word_regexp(regexp: &str) -> Result<Regex, &'static str> {
+    if regexp.is_empty() {
+        return Err("empty regular expression");
+    }
+    Regex::new(regexp.as_bytes())
+}
+
+impl<'a> EngineBuilder<'a> {
+    /// The `--warn-macro-sequence` regexp when none is given: `${...}` and
+    /// multi-digit `$10` references.
+    pub const DEFAULT_MACRO_SEQUENCE: &'static str = r"\$\({[^}]*}\|[0-9][0-9]+\)";
+
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            options: Options {
+                program_name: "m4".into(),
+                gnu: true,
+                nesting_limit: 1024,
+                word_regexp: None,
+                macro_sequence: None,
+                safer: false,
+                extensions: false,
+                shell: None,
+                debug_flags: "aeq".into(),
+                fatal_warnings: false,
+                quiet: false,
+                synclines: false,
+                prefix_builtins: false,
+                include_paths: Vec::new(),
+                traced: Vec::new(),
+            },
+            delimiters: Delimiters::new(),
+            output: None,
+            debug_output: None,
+            diagnostics: None,
+        }
+    }
+
+    /// Sets the name warnings are prefixed with.
+    #[must_use]
+    pub fn program_name(mut self, name: impl Into<String>) -> Self {
+        self.options.program_name = text_of(name.into().as_bytes());
+        self
+    }
+
+    /// Enables or disables GNU extensions.
+    #[must_use]
+    pub const fn gnu(mut self, gnu: bool) -> Self {
+        self.options.gnu = gnu;
+        self
+    }
+
+    /// Shorthand for `gnu(false)`, like `--traditional`.
+    #[must_use]
+    pub const fn traditional(self) -> Self {
+        self.gnu(false)
+    }
+
+    /// Limits how deeply macro calls may nest, counting calls in the
+    /// arguments of others; going deeper fails with
+    /// [`Error::NestingLimit`](crate::Error::NestingLimit). 0 means unlimited.
+    #[must_use]
+    pub const fn nesting_limit(mut self, limit: u64) -> Self {
+        self.options.nesting_limit = limit;
+        self
+    }
+
+    /// Replaces the regexp that recognizes macro names.
+    #[must_use]
+    pub fn word_regexp(mut self, regexp: impl Into<String>) -> Self {
+        self.options.word_regexp = Some(regexp.into());
+        self
+    }
+
+    /// Warns whenever `define` or `pushdef` is given a body containing a
+    /// match for `regexp`, such as [`DEFAULT_MACRO_SEQUENCE`] to catch
+    /// arguments that GNU m4 1.4 and POSIX read differently.
+    ///
+    /// [`DEFAULT_MACRO_SEQUENCE`]: Self::DEFAULT_MACRO_SEQUENCE
+    #[must_use]
+    pub fn warn_macro_sequence(mut self, regexp: impl Into<String>) -> Self {
+        self.options.macro_sequence = Some(regexp.into());
+        self
+    }
+
+    /// Sets the quote delimiters, which may be several characters long.
+    #[must_use]
+    pub fn quotes(mut self, start: impl Into<String>, end: impl Into<String>) -> Self {
+        self.delimiters.quote_start = text_of(start.into().as_bytes());
+        self.delimiters.quote_end = text_of(end.into().as_bytes());
+        self
+    }
+
+    /// Sets the comment delimiters; an empty `start` disables comments, and
+    /// an empty `end` means a newline.
+    #[must_use]
+    pub fn comments(mut self, start: impl Into<String>, end: impl Into<String>) -> Self {
+        self.delimiters.set_comments(
+            &text_of(start.into().as_bytes()),
+            &text_of(end.into().as_bytes()),
+        );
+        self
+    }
+
+    /// Makes every builtin reachable only as `m4_` and its name, like
+    /// `m4_define` and `m4___file__`, so the bare names are free for the
+    /// input to use. `builtin` and frozen files still go by the bare names.
+    #[must_use]
+    pub const fn prefix_builtins(mut self, prefix: bool) -> Self {
+        self.options.prefix_builtins = prefix;
+        self
+    }
+
+    /// Disables builtins that touch the system beyond input and output.
+    #[must_use]
+    pub const fn safer(mut self, safer: bool) -> Self {
+        self.options.safer = safer;
+        self
+    }
+
+    /// Enables lc-m4's own builtins, like `getenv`, which GNU m4 doesn't
+    /// have. They stay off in traditional mode.
+    #[must_use]
+    pub const fn extensions(mut self, extensions: bool) -> Self {
+        self.options.extensions = extensions;
+        self
+    }
+
+    /// Sets the shell used to run commands; conflicts with [`safer`](Self::safer).
+    #[must_use]
+    pub fn shell(mut self, shell: impl Into<String>) -> Self {
+        self.options.shell = Some(shell.into());
+        self
+    }
+
+    /// Sets the debug flags, the letters GNU m4's `-d` takes, such as `t`
+    /// to trace every call or `e` to show expansions; the default is `aeq`.
+    /// `debugmode` can change them while running.
+    #[must_use]
+    pub fn debug_flags(mut self, flags: impl Into<String>) -> Self {
+        self.options.debug_flags = flags.into();
+        self
+    }
+
+    /// Makes warnings fail the run, as errors do, though processing still
+    /// goes on. Warnings left out by [`quiet`](Self::quiet) don't count.
+    #[must_use]
+    pub const fn fatal_warnings(mut self, fatal: bool) -> Self {
+        self.options.fatal_warnings = fatal;
+        self
+    }
+
+    /// Leaves out warnings, such as about missing or excess arguments.
+    /// Errors are still reported.
+    #[must_use]
+    pub const fn quiet(mut self, quiet: bool) -> Self {
+        self.options.quiet = quiet;
+        self
+    }
+
+    /// Marks where output comes from with `#line` lines, as a C
+    /// preprocessor would, whenever it stops following the input lines.
+    #[must_use]
+    pub const fn synclines(mut self, synclines: bool) -> Self {
+        self.options.synclines = synclines;
+        self
+    }
+
+    /// Appends a directory to the include search path, where `include`,
+    /// `sinclude` and [`Engine::open_input`] look for relative names that
+    /// aren't in the current directory.
+    #[must_use]
+    pub fn include_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.include_paths.push(path.into());
+        self
+    }
+
+    /// Traces calls to the macro `name`.
+    #[must_use]
+    pub fn trace(mut self, name: impl Into<String>) -> Self {
+        self.options.traced.push(text_of(name.into().as_bytes()));
+        self
+    }
+
+    /// Sends expanded output to `sink` instead of stdout.
+    #[must_use]
+    pub fn output(mut self, sink: impl Write + 'a) -> Self {
+        self.output = Some(Box::new(sink));
+        self
+    }
+
+    /// Sends debug and trace output to `sink` instead of stderr.
+    #[must_use]
+    pub fn debug_output(mut self, sink: impl Write + 'a) -> Self {
+        self.debug_output = Some(Box::new(sink));
+        self
+    }
+
+    /// Sends warnings and error messages to `sink` instead of stderr.
+    #[must_use]
+    pub fn diagnostic_output(mut self, sink: impl Write + 'a) -> Self {
+        self.diagnostics = Some(Box::new(sink));
+        self
+    }
+
+    /// Validates the options and creates the engine.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the word regexp is malformed, or if a shell was configured in
+    /// safer mode.
+    ///
+    /// ```
+    /// use lc_m4::{BuildError, EngineBuilder};
+    ///
+    /// let err = EngineBuilder::new().safer(true).shell("/bin/sh").build().err();
+    /// assert_eq!(err, Some(BuildError::ShellInSaferMode));
+    /// assert!(EngineBuilder::new().word_regexp("[a-z").build().is_err());
+    /// ```
+    pub fn build(self) -> Result<Engine<'a>, BuildError> {
+        let word_regexp = self
+            .options
+            .word_regexp
+            .as_deref()
+            .map(|regexp| {
+                compile_word_regexp(regexp).map_err(|reason| BuildError::BadWordRegexp {
+                    regexp: regexp.into(),
+                    reason,
+                })
+            })
+            .transpose()?;
+        let macro_sequence = self
+            .options
+            .macro_sequence
+            .as_deref()
+            .map(|regexp| {
+                Regex::new(regexp.as_bytes()).map_err(|reason| BuildError::BadMacroSequence {
+                    regexp: regexp.into(),
+                    reason,
+                })
+            })
+            .transpose()?;
+        if self.options.safer && self.options.shell.is_some() {
+            return Err(BuildError::ShellInSaferMode);
+        }
+        let traced = self.options.traced.iter().cloned().collect();
+        let debug_flags = self.options.debug_flags.clone();
+        let symbols = builtins::initial_definitions(&self.options);
+        Ok(Engine {
+            options: self.options,
+            output: Sink::new(
+                self.output.unwrap_or_else(|| Box::new(io::stdout())),
+                Stream::Output,
+            ),
+            debug_output: Sink::new(
+                self.debug_output.unwrap_or_else(|| Box::new(io::stderr())),
+                Stream::Debug,
+            ),
+            diagnostics: Sink::new(
+                self.diagnostics.unwrap_or_else(|| Box::new(io::stderr())),
+                Stream::Diagnostics,
+            ),
+            symbols,
+            cur_diversion: 0,
+            diversion_data: BTreeMap::new(),
+            delimiters: self.delimiters,
+            input: Input::new(),
+            failed: false,
+            wrapped: Vec::new(),
+            word_regexp,
+            macro_sequence,
+            sysval: 0,
+            exit_code: None,
+            traced,
+            debug_flags,
+            expansion_level: 0,
+            discarded: 0,
+            output_line: None,
+            synced_file: String::new(),
+            start_of_output_line: true,
+        })
+    }
+}
+
+impl Default for EngineBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,951 @@
+//! The builtin macros.
+
+use std::collections::HashMap;
+use std::env;
+use std::io::Read;
+use std::num::IntErrorKind;
+
+use crate::engine::{
+    bytes_of, os_string_of, text_of, Engine, MacroValue, Options, DEFAULT_QUOTE_END,
+    DEFAULT_QUOTE_START,
+};
+use crate::error::{os_reason, Error};
+use crate::eval;
+use crate::input::{Input, Item};
+use crate::regex::{Captures, Regex};
+use crate::symtab::SymbolTable;
+use crate::system::traditional_maketemp;
+
+/// A builtin and how it's called. Definitions refer to the table entry
+/// itself, so a builtin keeps its identity whatever name it's defined under.
+pub struct Builtin {
+    name: &'static str,
+    /// Only recognized when followed by `(`; alone, the name is plain text.
+    blind: bool,
+    /// The fewest arguments it can be called with.
+    min: usize,
+    /// The most arguments it uses; more are warned about.
+    max: usize,
+}
+
+impl Builtin {
+    /// The name the builtin is predefined as, and known by to `builtin`.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Whether the builtin is blind: only called when a `(` follows.
+    pub const fn is_blind(&self) -> bool {
+        self.blind
+    }
+}
+
+/// No upper limit on the number of arguments.
+const MANY: usize = usize::MAX;
+
+/// A builtin that's called with or without arguments.
+const fn plain(name: &'static str, min: usize, max: usize) -> Builtin {
+    Builtin {
+        name,
+        blind: false,
+        min,
+        max,
+    }
+}
+
+/// A builtin that's only called with arguments.
+const fn blind(name: &'static str, min: usize, max: usize) -> Builtin {
+    Builtin {
+        name,
+        blind: true,
+        min,
+        max,
+    }
+}
+
+/// The builtins every engine starts out with.
+const BUILTINS: &[Builtin] = &[
+    plain("changecom", 0, 2),
+    plain("changequote", 0, 2),
+    blind("decr", 1, 1),
+    blind("define", 1, 2),
+    blind("defn", 1, MANY),
+    plain("divert", 0, 1),
+    plain("divnum", 0, 0),
+    plain("dnl", 0, 0),
+    plain("dumpdef", 0, MANY),
+    blind("errprint", 1, MANY),
+    blind("eval", 1, 3),
+    blind("ifdef", 2, 3),
+    blind("ifelse", 3, MANY),
+    blind("include", 1, 1),
+    blind("index", 2, 2),
+    blind("incr", 1, 1),
+    blind("len", 1, 1),
+    plain("m4exit", 0, 1),
+    blind("m4wrap", 1, MANY),
+    blind("maketemp", 1, 1),
+    blind("mkstemp", 1, 1),
+    blind("popdef", 1, MANY),
+    blind("pushdef", 1, 2),
+    blind("shift", 1, MANY),
+    blind("sinclude", 1, 1),
+    blind("substr", 2, 3),
+    blind("syscmd", 1, 1),
+    plain("sysval", 0, 0),
+    plain("traceoff", 0, MANY),
+    plain("traceon", 0, MANY),
+    blind("translit", 2, 3),
+    blind("undefine", 1, MANY),
+    plain("undivert", 0, MANY),
+];
+
+/// Builtins that only exist with GNU extensions enabled.
+const GNU_BUILTINS: &[Builtin] = &[
+    plain("__file__", 0, 0),
+    plain("__lc_m4__", 0, 0),
+    plain("__line__", 0, 0),
+    plain("__program__", 0, 0),
+    blind("builtin", 1, MANY),
+    blind("changeword", 1, 1),
+    plain("debugfile", 0, 1),
+    plain("debugmode", 0, 1),
+    blind("esyscmd", 1, 1),
+    blind("format", 1, MANY),
+    blind("indir", 1, MANY),
+    blind("patsubst", 2, 3),
+    blind("regexp", 2, 3),
+];
+
+/// lc-m4's own builtins, which GNU m4 lacks; they need GNU mode and
+/// [`extensions`](crate::EngineBuilder::extensions).
+const EXTENSION_BUILTINS: &[Builtin] = &[blind("getenv", 1, 1), blind("mkdtemp", 1, 1)];
+
+/// How many input files may be open at once through `include`. Files that
+/// include each other can legitimately nest a little, but not forever.
+const INCLUDE_DEPTH_LIMIT: usize = 256;
+
+/// Builtins that reach outside m4, left out in safer mode.
+const SYSTEM_BUILTINS: &[&str] = &[
+    "esyscmd", "getenv", "maketemp", "mkdtemp", "mkstemp", "syscmd",
+];
+
+/// The builtins an engine with these options has.
+fn available_builtins(options: &Options) -> impl Iterator<Item = &'static Builtin> {
+    let gnu_builtins = if options.gnu { GNU_BUILTINS } else { &[] };
+    let extensions = if options.gnu && options.extensions {
+        EXTENSION_BUILTINS
+    } else {
+        &[]
+    };
+    let safer = options.safer;
+    BUILTINS
+        .iter()
+        .chain(gnu_builtins)
+        .chain(extensions)
+        .filter(move |builtin| !safer || !SYSTEM_BUILTINS.contains(&builtin.name))
+}
+
+/// The builtin called `name` that an engine with these options has.
+pub fn find_builtin(options: &Options, name: &str) -> Option<&'static Builtin> {
+    available_builtins(options).find(|builtin| builtin.name == name)
+}
+
+/// The symbol table of a fresh engine: one definition per builtin, named
+/// `m4_` and its name if builtins are prefixed. The platform macros keep
+/// their names either way, like in GNU m4 1.4.
+pub fn initial_definitions(options: &Options) -> SymbolTable {
+    let mut symbols = SymbolTable::new();
+    for builtin in available_builtins(options) {
+        let value = MacroValue::BuiltinFunction(builtin);
+        if options.prefix_builtins {
+            symbols.define(&format!("m4_{}", builtin.name), value);
+        } else {
+            symbols.define(builtin.name, value);
+        }
+    }
+    // Empty macros that tell scripts where they are running
+    let platform = if cfg!(windows) { "windows" } else { "unix" };
+    if options.gnu {
+        symbols.define("__gnu__", MacroValue::Text(String::new()));
+        symbols.define(&format!("__{platform}__"), MacroValue::Text(String::new()));
+    } else {
+        symbols.define(platform, MacroValue::Text(String::new()));
+    }
+    // So scripts can work around how lc-m4 differs; like `__gnu__`, it
+    // stays out of the traditional namespace
+    if options.gnu {
+        symbols.define("__lc_m4_version__", MacroValue::Text(crate::VERSION.into()));
+    }
+    symbols
+}
+
+/// The text of argument `i`, empty if it's missing or a builtin.
+fn arg(args: &[MacroValue], i: usize) -> &str {
+    args.get(i).map_or("", MacroValue::text)
+}
+
+/// Expands `a-z` style ranges like GNU m4: a range may run backwards, and a
+/// `-` at either end is literal.
+fn expand_ranges(set: &str) -> Vec<char> {
+    let mut expanded = Vec::new();
+    let mut chars = set.chars();
+    let mut prev = None;
+    while let Some(c) = chars.next() {
+        match prev {
+            Some(from) if c == '-' => {
+                let Some(to) = chars.next() else {
+                    expanded.push('-');
+                    break;
+                };
+                if from <= to {
+                    expanded.extend((from..=to).skip(1));
+                } else {
+                    expanded.extend((to..from).rev());
+                }
+                prev = Some(to);
+            }
+            _ => {
+                expanded.push(c);
+                prev = Some(c);
+            }
+        }
+    }
+    expanded
+}
+
+/// Maps each char of `text` found in `from` to the char at the same place
+/// in `to`, deleting it if `to` is too short. The first mapping of a
+/// repeated char wins.
+fn translit(text: &str, from: &[char], to: &[char]) -> String {
+    let mut map = HashMap::new();
+    for (i, &c) in from.iter().enumerate() {
+        map.entry(c).or_insert_with(|| to.get(i).copied());
+    }
+    text.chars()
+        .filter_map(|c| map.get(&c).map_or(Some(c), |&mapped| mapped))
+        .collect()
+}
+
+impl Engine<'_> {
+    /// Queues an expansion to be rescanned.
+    fn push_text(&mut self, text: &str) {
+        self.input.push(bytes_of(text));
+    }
+
+    /// Wraps `text` in the current quotes, so rescanning yields it verbatim.
+    pub(crate) fn quote(&self, text: &str) -> String {
+        let mut quoted = String::new();
+        quoted.push_str(&self.delimiters.quote_start);
+        quoted.push_str(text);
+        quoted.push_str(&self.delimiters.quote_end);
+        quoted
+    }
+
+    /// Parses a numeric argument the way GNU m4 does, warning about anything
+    /// odd. Returns `None` if the argument isn't a number at all.
+    fn numeric_arg(&mut self, builtin: &str, text: &str) -> Result<Option<i64>, Error> {
+        if text.is_empty() {
+            self.warn(&format!("empty string treated as 0 in builtin `{builtin}'"))?;
+            return Ok(Some(0));
+        }
+        let trimmed = text.trim_start_matches(|c: char| c.is_ascii_whitespace());
+        let value = match trimmed.parse::<i64>() {
+            Ok(value) => value,
+            Err(e) => match e.kind() {
+                IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                    self.warn(&format!("numeric overflow detected in builtin `{builtin}'"))?;
+                    if *e.kind() == IntErrorKind::PosOverflow {
+                        i64::MAX
+                    } else {
+                        i64::MIN
+                    }
+                }
+                _ => {
+                    self.warn(&format!("non-numeric argument to builtin `{builtin}'"))?;
+                    return Ok(None);
+                }
+            },
+        };
+        if trimmed.len() != text.len() {
+            self.warn(&format!(
+                "leading whitespace ignored in builtin `{builtin}'"
+            ))?;
+        }
+        Ok(Some(value))
+    }
+
+    /// Appends diversion `number` to the current one and empties it. The
+    /// current diversion and the output itself can't be undiverted.
+    fn undivert(&mut self, number: i64) -> Result<(), Error> {
+        if number == self.cur_diversion {
+            return Ok(());
+        }
+        if let Some(text) = self.diversion_data.remove(&number) {
+            self.print_to_diversion(&text)?;
+        }
+        Ok(())
+    }
+
+    /// Copies a file into the current diversion without scanning it. Like
+    /// `include`, this looks along the include path.
+    fn undivert_file(&mut self, path: &str) -> Result<(), Error> {
+        let data = self.open_include(path).and_then(|mut file| {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            Ok(data)
+        });
+        match data {
+            Ok(data) => self.print_to_diversion(&text_of(&data)),
+            Err(e) => self.warn(&format!("cannot undivert `{path}': {}", os_reason(&e))),
+        }
+    }
+
+    /// Queues an argument as an expansion, keeping builtin tokens intact.
+    fn push_value(&mut self, value: &MacroValue) {
+        match value {
+            MacroValue::Text(text) => self.push_text(text),
+            MacroValue::BuiltinFunction(builtin) => self.input.push_builtin(builtin),
+        }
+    }
+
+    /// Checks the number of arguments to `builtin`, warning like GNU m4 if
+    /// it's off. Returns whether there are enough to call it.
+    fn check_arity(&mut self, builtin: &Builtin, args: &[MacroValue]) -> Result<bool, Error> {
+        let name = builtin.name;
+        if args.len() < builtin.min {
+            self.warn(&format!("too few arguments to builtin `{name}'"))?;
+            return Ok(false);
+        }
+        if args.len() > builtin.max {
+            self.warn(&format!("excess arguments to builtin `{name}' ignored"))?;
+        }
+        Ok(true)
+    }
+
+    #[allow(clippy::too_many_lines)]
+    pub(crate) fn call_builtin(
+        &mut self,
+        builtin: &Builtin,
+        args: &[MacroValue],
+    ) -> Result<(), Error> {
+        let name = builtin.name;
+        // `ifelse(text)` is an idiom for a comment, so it's silent
+        if name == "ifelse" && args.len() == 1 {
+            return Ok(());
+        }
+        if !self.check_arity(builtin, args)? {
+            // Like GNU m4, a few builtins still expand to something given
+            // just their first argument
+            match args {
+                [_] if matches!(name, "index" | "regexp") => self.push_text("0"),
+                [text] if matches!(name, "patsubst" | "substr" | "translit") => {
+                    self.push_value(text);
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+        match name {
+            "__file__" => {
+                let file = self.quote(&self.input.location().file);
+                self.push_text(&file);
+            }
+            "__lc_m4__" => self.push_text(&self.quote(&format!("lc-m4 {}", crate::VERSION))),
+            "__line__" => self.push_text(&self.input.location().line.to_string()),
+            "__program__" => self.push_text(&self.quote(&self.options.program_name)),
+            "builtin" => {
+                // Found by its own name, whatever it's defined as now
+                if let [target, rest @ ..] = args {
+                    let target = target.text();
+                    if let Some(builtin) = find_builtin(&self.options, target) {
+                        self.call_builtin(builtin, rest)?;
+                    } else {
+                        self.warn(&format!("undefined builtin `{target}'"))?;
+                    }
+                }
+            }
+            "changecom" => {
+                // GNU m4 disables comments when called without arguments or
+                // with an empty start
+                self.delimiters.set_comments(arg(args, 0), arg(args, 1));
+            }
+            "changequote" => {
+                // Like GNU m4: no arguments restore the defaults, and a
+                // missing or empty end quote next to a real start quote
+                // falls back to the default one
+                let (start, end) = match args {
+                    [] => (DEFAULT_QUOTE_START, DEFAULT_QUOTE_END),
+                    [start] => (start.text(), DEFAULT_QUOTE_END),
+                    [start, end, ..] if !start.text().is_empty() && end.text().is_empty() => {
+                        (start.text(), DEFAULT_QUOTE_END)
+                    }
+                    [start, end, ..] => (start.text(), end.text()),
+                };
+                self.delimiters.quote_start = start.into();
+                self.delimiters.quote_end = end.into();
+            }
+            "changeword" => {
+                let regexp = arg(args, 0);
+                if regexp.is_empty() {
+                    self.word_regexp = None;
+                } else {
+                    match Regex::new(&bytes_of(regexp)) {
+                        Ok(compiled) => self.word_regexp = Some(compiled),
+                        Err(reason) => {
+                            let message = format!("bad regular expression `{regexp}': {reason}");
+                            self.warn(&message)?;
+                        }
+                    }
+                }
+            }
+            "debugfile" => self.debugfile(args)?,
+            "debugmode" => self.debugmode(args)?,
+            "decr" | "incr" => {
+                if let Some(number) = args.first() {
+                    if let Some(value) = self.int_arg(name, number.text())? {
+                        let step = if name == "incr" { 1 } else { -1 };
+                        self.push_text(&value.wrapping_add(step).to_string());
+                    }
+                }
+            }
+            "define" => {
+                if let Some(name) = args.first() {
+                    let value = args.get(1).cloned().unwrap_or_default();
+                    self.check_definition(name.text(), &value)?;
+                    self.symbols.define(name.text(), value);
+                }
+            }
+            "defn" => {
+                for name in args.iter().rev() {
+                    match self.symbols.lookup(name.text()) {
+                        Some(MacroValue::Text(body)) => {
+                            let quoted = self.quote(body);
+                            self.push_text(&quoted);
+                        }
+                        Some(MacroValue::BuiltinFunction(builtin)) => {
+                            self.input.push_builtin(builtin);
+                        }
+                        None => {}
+                    }
+                }
+            }
+            "divert" => {
+                let diversion = match args.first() {
+                    Some(number) => self.int_arg(name, number.text())?,
+                    None => Some(0),
+                };
+                if let Some(diversion) = diversion {
+                    self.report_discarded()?;
+                    self.cur_diversion = i64::from(diversion);
+                    self.output_line = None;
+                }
+            }
+            "divnum" => self.push_text(&self.cur_diversion.to_string()),
+            "dnl" => {
+                // Only the rest of the current file is discarded: its end
+                // counts as the newline, reported where the `dnl` was
+                let input = &self.input;
+                let start = input
+                    .ended_file()
+                    .unwrap_or_else(|| input.location())
+                    .clone();
+                loop {
+                    // Builtin tokens on the way are discarded like text
+                    match self.input.next_item_in_file() {
+                        Some(Item::Byte(b'\n')) => break,
+                        Some(_) => {}
+                        None => {
+                            let message = format!("{name}: end of file treated as newline");
+                            self.warn_at(&start, &message)?;
+                            break;
+                        }
+                    }
+                }
+            }
+            "eval" => self.eval(name, args)?,
+            "dumpdef" => self.dumpdef(args)?,
+            "errprint" => {
+                let text = args
+                    .iter()
+                    .map(MacroValue::text)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                self.diagnose(&text)?;
+            }
+            "esyscmd" => {
+                if let Some(command) = args.first() {
+                    self.esyscmd(command.text())?;
+                }
+            }
+            "format" => {
+                let text = self.format(args)?;
+                self.push_text(&text);
+            }
+            "getenv" => {
+                // Quoted, so the value comes out as it is; unset is empty
+                let value = env::var_os(os_string_of(arg(args, 0)))
+                    .map(|value| text_of(&value.into_encoded_bytes()))
+                    .unwrap_or_default();
+                self.push_text(&self.quote(&value));
+            }
+            "ifdef" => {
+                let defined = self.symbols.lookup(arg(args, 0)).is_some();
+                let expansion = arg(args, if defined { 1 } else { 2 });
+                self.push_text(expansion);
+            }
+            "ifelse" => {
+                // Compare pairs until one matches. Like GNU m4, when four or
+                // five arguments are left, the fourth is the default and a
+                // fifth is ignored, so 5, 8, 11... arguments are too many
+                // (GNU m4's `argc` counts the macro name too)
+                let count = args.len() + 1;
+                if (count + 2) % 3 > 1 {
+                    self.warn(&format!("excess arguments to builtin `{name}' ignored"))?;
+                }
+                let mut rest = args;
+                loop {
+                    match rest {
+                        [a, b, result, ..] if a.text() == b.text() => {
+                            self.push_value(result);
+                            break;
+                        }
+                        [_, _, _, default] | [_, _, _, default, _] => {
+                            self.push_value(default);
+                            break;
+                        }
+                        [_, _, _, _, _, _, ..] => rest = &rest[3..],
+                        _ => break,
+                    }
+                }
+            }
+            "indir" => {
+                if let [target, rest @ ..] = args {
+                    let target = target.text();
+                    match self.symbols.lookup(target).cloned() {
+                        Some(value) => self.call_macro(target, &value, rest)?,
+                        None => self.warn(&format!("undefined macro `{target}'"))?,
+                    }
+                }
+            }
+            "include" | "sinclude" => {
+                if let Some(file) = args.first() {
+                    let file = file.text();
+                    if self.input.files().count() >= INCLUDE_DEPTH_LIMIT {
+                        let message = if self.input.files().any(|active| active == file) {
+                            format!("recursive include of file `{file}'")
+                        } else {
+                            format!("includes nested too deeply at file `{file}'")
+                        };
+                        return self.error(&message);
+                    }
+                    let data = self.open_include(file).and_then(|mut f| {
+                        let mut data = Vec::new();
+                        f.read_to_end(&mut data)?;
+                        Ok(data)
+                    });
+                    match data {
+                        Ok(data) => self.input.push_file(file.into(), data),
+                        Err(e) if name == "include" => {
+                            let reason = os_reason(&e);
+                            self.error(&format!("cannot open file `{file}': {reason}"))?;
+                        }
+                        Err(_) => {}
+                    }
+                }
+            }
+            // Text holds one char per input byte, so like GNU m4 this
+            // counts bytes
+            "len" => self.push_text(&arg(args, 0).chars().count().to_string()),
+            "index" => {
+                let (text, wanted) = (arg(args, 0), arg(args, 1));
+                let position = text.find(wanted).map_or(-1, |at| {
+                    i64::try_from(text[..at].chars().count()).unwrap_or(-1)
+                });
+                self.push_text(&position.to_string());
+            }
+            "m4exit" => {
+                let code = match args.first() {
+                    Some(code) => self.int_arg(name, code.text())?.unwrap_or(1),
+                    None => 0,
+                };
+                let code = if let Ok(code) = u8::try_from(code) {
+                    i32::from(code)
+                } else {
+                    self.warn(&format!("exit status out of range: `{code}'"))?;
+                    1
+                };
+                // Unlike the end of input, this skips wrapped text; diverted
+                // text is still flushed when the run finishes
+                self.exit_code = Some(code);
+                self.input = Input::new();
+                self.wrapped.clear();
+            }
+            "m4wrap" => {
+                // GNU m4 saves all the arguments, separated by spaces
+                let text = if self.options.gnu {
+                    args.iter()
+                        .map(MacroValue::text)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                } else {
+                    arg(args, 0).into()
+                };
+                self.wrapped.push(text);
+            }
+            "maketemp" if !self.options.gnu => {
+                if let Some(template) = args.first() {
+                    self.push_text(&traditional_maketemp(template.text()));
+                }
+            }
+            "maketemp" | "mkstemp" => {
+                if let Some(template) = args.first() {
+                    if name == "maketemp" {
+                        self.warn("recommend using mkstemp instead")?;
+                    }
+                    if let Some(file) = self.mkstemp(template.text())? {
+                        self.push_text(&self.quote(&file));
+                    }
+                }
+            }
+            "mkdtemp" => {
+                if let Some(template) = args.first() {
+                    if let Some(dir) = self.mkdtemp(template.text())? {
+                        self.push_text(&self.quote(&dir));
+                    }
+                }
+            }
+            "patsubst" => self.patsubst(args)?,
+            "pushdef" => {
+                if let Some(name) = args.first() {
+                    let value = args.get(1).cloned().unwrap_or_default();
+                    self.check_definition(name.text(), &value)?;
+                    self.symbols.pushdef(name.text(), value);
+                }
+            }
+            "popdef" => {
+                for name in args {
+                    self.symbols.popdef(name.text());
+                }
+            }
+            "regexp" => self.regexp(args)?,
+            "shift" => {
+                let rest: Vec<String> = args
+                    .iter()
+                    .skip(1)
+                    .map(|arg| self.quote(arg.text()))
+                    .collect();
+                self.push_text(&rest.join(","));
+            }
+            "substr" => self.substr(name, args)?,
+            "syscmd" => {
+                if let Some(command) = args.first() {
+                    self.syscmd(command.text())?;
+                }
+            }
+            "sysval" => self.push_text(&self.sysval.to_string()),
+            "traceoff" | "traceon" => {
+                // Without arguments, every macro defined so far
+                let targets: Vec<String> = if args.is_empty() {
+                    self.symbols
+                        .sorted()
+                        .into_iter()
+                        .map(|(target, ..)| target.into())
+                        .collect()
+                } else {
+                    args.iter().map(|arg| arg.text().into()).collect()
+                };
+                for target in targets {
+                    if name == "traceon" {
+                        self.traced.insert(target);
+                    } else {
+                        self.traced.remove(&target);
+                    }
+                }
+            }
+            "translit" => {
+                if let [text, rest @ ..] = args {
+                    let from = expand_ranges(arg(rest, 0));
+                    let to = expand_ranges(arg(rest, 1));
+                    let translated = translit(text.text(), &from, &to);
+                    self.push_text(&translated);
+                }
+            }
+            "undefine" => {
+                for name in args {
+                    self.symbols.undefine(name.text());
+                }
+            }
+            "undivert" => {
+                if args.is_empty() {
+                    let numbers: Vec<i64> = self.diversion_data.keys().copied().collect();
+                    for number in numbers {
+                        self.undivert(number)?;
+                    }
+                }
+                for arg in args {
+                    // Like strtol, but leading whitespace makes it a file name
+                    let text = arg.text();
+                    let number = if text.is_empty() {
+                        Some(0)
+                    } else if text.starts_with(|c: char| c.is_ascii_whitespace()) {
+                        None
+                    } else {
+                        text.parse().ok()
+                    };
+                    match number {
+                        Some(number) => self.undivert(number)?,
+                        None if self.options.gnu => self.undivert_file(text)?,
+                        None => {
+                            self.warn(&format!("non-numeric argument to builtin `{name}'"))?;
+                        }
+                    }
+                }
+            }
+            _ => unreachable!("builtin `{name}' is in a builtin table but has no implementation"),
+        }
+        Ok(())
+    }
+
+    /// Parses a numeric argument into the C `int` GNU m4 reads: the value,
+    /// saturated to a `long` on overflow, is cut to its low 32 bits, so
+    /// values past the `int` range wrap around.
+    #[allow(clippy::cast_possible_truncation)]
+    fn int_arg(&mut self, builtin: &str, text: &str) -> Result<Option<i32>, Error> {
+        Ok(self.numeric_arg(builtin, text)?.map(|value| value as i32))
+    }
+
+    /// `substr(text, from, [length])`, following GNU m4: a negative start
+    /// or a start past the end gives nothing, a length running past the end
+    /// is cut short, and with no bounds at all the whole text comes back.
+    /// The bounds are C `int`s, so huge ones wrap around.
+    fn substr(&mut self, name: &str, args: &[MacroValue]) -> Result<(), Error> {
+        let text: Vec<char> = arg(args, 0).chars().collect();
+        let Some(start) = self.int_arg(name, arg(args, 1))? else {
+            return Ok(());
+        };
+        let start = i64::from(start);
+        let avail = i64::try_from(text.len()).unwrap_or(i64::MAX);
+        let length = match args.get(2) {
+            Some(length) => match self.int_arg(name, length.text())? {
+                Some(length) => i64::from(length),
+                None => return Ok(()),
+            },
+            None => avail,
+        };
+        if start < 0 || length <= 0 || start >= avail {
+            return Ok(());
+        }
+        let end = start.saturating_add(length).min(avail);
+        let range = usize::try_from(start).unwrap_or(0)..usize::try_from(end).unwrap_or(0);
+        self.push_text(&text[range].iter().collect::<String>());
+        Ok(())
+    }
+
+    /// `eval(expression, [radix], [width])`: the value of `expression`
+    /// written in `radix`, zero-padded to `width` digits. Like GNU m4 1.4,
+    /// the arithmetic is on 32-bit ints whatever the platform, and so are
+    /// the radix and width.
+    fn eval(&mut self, name: &str, args: &[MacroValue]) -> Result<(), Error> {
+        let Some(expression) = args.first() else {
+            return Ok(());
+        };
+        let radix = match arg(args, 1) {
+            "" => 10,
+            radix => match self.int_arg(name, radix)? {
+                Some(radix) => radix,
+                None => return Ok(()),
+            },
+        };
+        let Some(radix) = u32::try_from(radix).ok().filter(|r| (1..=36).contains(r)) else {
+            return self.warn(&format!(
+                "radix in builtin `{name}' out of range (radix = {radix})"
+            ));
+        };
+        let width = match args.get(2) {
+            Some(width) => match self.int_arg(name, width.text())? {
+                Some(width) => width,
+                None => return Ok(()),
+            },
+            None => 1,
+        };
+        let Ok(width) = usize::try_from(width) else {
+            return self.warn(&format!("negative width to builtin `{name}'"));
+        };
+        // Like GNU m4, nothing at all counts as 0, but not blanks
+        if expression.text().is_empty() {
+            self.warn(&format!("empty string treated as 0 in builtin `{name}'"))?;
+            self.push_text(&eval::render(0, radix, width));
+            return Ok(());
+        }
+        match eval::evaluate(expression.text()) {
+            Ok(value) => self.push_text(&eval::render(value, radix, width)),
+            Err(e) => self.error(&format!("{e}: {}", expression.text()))?,
+        }
+        Ok(())
+    }
+
+    /// Warns about a body being given to `name` that holds a NUL byte, which
+    /// text elsewhere may end at, then checks it for macro sequences.
+    fn check_definition(&mut self, name: &str, value: &MacroValue) -> Result<(), Error> {
+        if value.text().contains('\0') {
+            self.warn(&format!("definition of `{name}' contains a NUL byte"))?;
+        }
+        self.check_macro_sequence(name, value)
+    }
+
+    /// Warns about each nonempty match of the `--warn-macro-sequence`
+    /// regexp in the body being given to `name`.
+    fn check_macro_sequence(&mut self, name: &str, value: &MacroValue) -> Result<(), Error> {
+        let (Some(regex), MacroValue::Text(body)) = (&self.macro_sequence, value) else {
+            return Ok(());
+        };
+        let body = bytes_of(body);
+        let mut searcher = regex.searcher(&body);
+        let mut sequences = Vec::new();
+        let mut offset = 0;
+        while let Some(captures) = searcher.search(offset) {
+            let Some((start, end)) = captures[0] else {
+                break;
+            };
+            if start == end {
+                offset = end + 1;
+            } else {
+                sequences.push(text_of(&body[start..end]));
+                offset = end;
+            }
+        }
+        for sequence in sequences {
+            self.warn(&format!(
+                "definition of `{name}' contains sequence `{sequence}'"
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Compiles the regexp argument of `regexp` or `patsubst`, warning if
+    /// it's malformed.
+    fn compile_regexp(&mut self, pattern: &str) -> Result<Option<Regex>, Error> {
+        match Regex::new(&bytes_of(pattern)) {
+            Ok(regex) => Ok(Some(regex)),
+            Err(reason) => {
+                self.warn(&format!("bad regular expression: `{pattern}': {reason}"))?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// `patsubst(text, regexp, [replacement])`: replaces every match. An
+    /// empty match keeps the character after it, so scanning moves on.
+    fn patsubst(&mut self, args: &[MacroValue]) -> Result<(), Error> {
+        let [text, pattern, replacement @ ..] = args else {
+            return Ok(());
+        };
+        let Some(regex) = self.compile_regexp(pattern.text())? else {
+            return Ok(());
+        };
+        let text = bytes_of(text.text());
+        let replacement = bytes_of(arg(replacement, 0));
+        let mut searcher = regex.searcher(&text);
+        let mut result = Vec::new();
+        let mut offset = 0;
+        while offset <= text.len() {
+            let Some(captures) = searcher.search(offset) else {
+                result.extend_from_slice(&text[offset..]);
+                break;
+            };
+            let Some((start, end)) = captures[0] else {
+                break;
+            };
+            result.extend_from_slice(&text[offset..start]);
+            result.extend(self.substitute(&text, &replacement, &captures)?);
+            offset = end;
+            if start == end {
+                result.extend(text.get(offset));
+                offset += 1;
+            }
+        }
+        self.push_text(&text_of(&result));
+        Ok(())
+    }
+
+    /// `regexp(text, regexp, [replacement])`: the offset of the first match,
+    /// or -1, or the replacement expanded for the first match.
+    fn regexp(&mut self, args: &[MacroValue]) -> Result<(), Error> {
+        let [text, pattern, replacement @ ..] = args else {
+            return Ok(());
+        };
+        let Some(regex) = self.compile_regexp(pattern.text())? else {
+            return Ok(());
+        };
+        let text = bytes_of(text.text());
+        let found = regex.search(&text, 0);
+        match replacement.first() {
+            None => {
+                let offset = found
+                    .and_then(|captures| captures[0])
+                    .map_or(-1, |(start, _)| i64::try_from(start).unwrap_or(-1));
+                self.push_text(&offset.to_string());
+            }
+            Some(replacement) => {
+                if let Some(captures) = found {
+                    let replacement = bytes_of(replacement.text());
+                    let expanded = self.substitute(&text, &replacement, &captures)?;
+                    self.push_text(&text_of(&expanded));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Expands a replacement for a match in `text`: `\&` and `\0` stand for
+    /// the whole match, `\1` to `\9` for groups, and any other escaped
+    /// character for itself.
+    fn substitute(
+        &mut self,
+        text: &[u8],
+        replacement: &[u8],
+        captures: &Captures,
+    ) -> Result<Vec<u8>, Error> {
+        let mut result = Vec::new();
+        let mut bytes = replacement.iter().copied();
+        while let Some(c) = bytes.next() {
+            if c != b'\\' {
+                result.push(c);
+                continue;
+            }
+            match bytes.next() {
+                Some(c @ (b'0'..=b'9' | b'&')) => {
+                    let group = if c == b'&' { 0 } else { usize::from(c - b'0') };
+                    match captures.get(group) {
+                        Some(Some((start, end))) => result.extend_from_slice(&text[*start..*end]),
+                        Some(None) => {}
+                        None => self.warn(&format!("sub-expression {group} not present"))?,
+                    }
+                }
+                Some(c) => result.push(c),
+                None => self.warn("trailing \\ ignored in replacement")?,
+            }
+        }
+        Ok(result)
+    }
+
+    /// Defines `name` as `value`, like `define` would, replacing the
+    /// current definition.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a warning about the body can't be written.
+    pub fn define(&mut self, name: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Result<(), Error> {
+        let name = text_of(name.as_ref());
+        let value = MacroValue::Text(text_of(value.as_ref()));
+        self.check_definition(&name, &value)?;
+        self.symbols.define(&name, value);
+        Ok(())
+    }
+
+    /// Removes every definition of `name`, including stacked ones.
+    pub fn undefine(&mut self, name: impl AsRef<[u8]>) {
+        self.symbols.undefine(&text_of(name.as_ref()));
+    }
+}
@@ -0,0 +1,142 @@
+//! Whole-table definition dumps, as written by `--dump-definitions`.
+
+use std::io::{self, Write};
+use std::str;
+
+use crate::engine::{bytes_of, Engine, MacroValue};
+use crate::error::Error;
+
+/// Output formats for [`Engine::dump_definitions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// One `name:<TAB>body` line per macro, like the `dumpdef` builtin.
+    Text,
+    /// A JSON array of objects with `name`, `kind`, `body`, `traced` and
+    /// `pushdef_depth` keys.
+    Json,
+}
+
+impl DumpFormat {
+    /// Parses a format name as given after the colon in `FILE:FORMAT`.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `text` as a JSON value. Valid UTF-8 becomes a string; anything else
+/// becomes an array of its byte values, so no input is ever lost or mangled.
+///
+/// # Errors
+///
+/// Fails if writing to `out` fails.
+pub fn write_json_text<W: Write + ?Sized>(out: &mut W, text: &[u8]) -> io::Result<()> {
+    let Ok(text) = str::from_utf8(text) else {
+        out.write_all(b"[")?;
+        for (i, b) in text.iter().enumerate() {
+            if i != 0 {
+                out.write_all(b",")?;
+            }
+            write!(out, "{b}")?;
+        }
+        return out.write_all(b"]");
+    };
+    out.write_all(b"\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => out.write_all(b"\\\"")?,
+            '\\' => out.write_all(b"\\\\")?,
+            '\n' => out.write_all(b"\\n")?,
+            '\r' => out.write_all(b"\\r")?,
+            '\t' => out.write_all(b"\\t")?,
+            c if u32::from(c) < 0x20 => write!(out, "\\u{:04x}", u32::from(c))?,
+            c => write!(out, "{c}")?,
+        }
+    }
+    out.write_all(b"\"")
+}
+
+impl Engine<'_> {
+    /// One `name:<TAB>body` line as `dumpdef` writes it, the body quoted
+    /// when the `q` debug flag is set.
+    fn definition_line(&self, name: &str, value: &MacroValue) -> String {
+        match value {
+            MacroValue::Text(body) if self.debug_flags.contains('q') => format!(
+                "{name}:\t{}{body}{}\n",
+                self.delimiters.quote_start, self.delimiters.quote_end
+            ),
+            MacroValue::Text(body) => format!("{name}:\t{body}\n"),
+            MacroValue::BuiltinFunction(builtin) => format!("{name}:\t<{}>\n", builtin.name()),
+        }
+    }
+
+    /// `dumpdef([names...])`: writes the definitions of `names`, or of every
+    /// macro, to the debug output. Like GNU m4, they come out sorted.
+    pub(crate) fn dumpdef(&mut self, args: &[MacroValue]) -> Result<(), Error> {
+        let mut defs: Vec<(String, MacroValue)> = if args.is_empty() {
+            self.symbols
+                .sorted()
+                .into_iter()
+                .map(|(name, value, _)| (name.into(), value.clone()))
+                .collect()
+        } else {
+            let mut defs = Vec::new();
+            for name in args.iter().map(MacroValue::text) {
+                match self.symbols.lookup(name) {
+                    Some(value) => defs.push((name.into(), value.clone())),
+                    None => self.warn(&format!("undefined macro `{name}'"))?,
+                }
+            }
+            defs
+        };
+        defs.sort_by(|a, b| a.0.cmp(&b.0));
+        let text: String = defs
+            .iter()
+            .map(|(name, value)| self.definition_line(name, value))
+            .collect();
+        self.debug_output.write(&bytes_of(&text))
+    }
+
+    /// Writes the complete macro table to `out`, independent of any `dumpdef`
+    /// calls in the input.
+    ///
+    /// # Errors
+    ///
+    /// Fails if writing to `out` fails.
+    pub fn dump_definitions<W: Write + ?Sized>(
+        &self,
+        out: &mut W,
+        format: DumpFormat,
+    ) -> io::Result<()> {
+        let defs = self.symbols.sorted();
+        match format {
+            DumpFormat::Text => {
+                for (name, value, _) in defs {
+                    out.write_all(&bytes_of(&self.definition_line(name, value)))?;
+                }
+            }
+            DumpFormat::Json => {
+                out.write_all(b"[")?;
+                for (i, (name, value, depth)) in defs.into_iter().enumerate() {
+                    out.write_all(if i == 0 { b"\n" } else { b",\n" })?;
+                    let (kind, body) = match value {
+                        MacroValue::Text(body) => ("text", body.as_str()),
+                        MacroValue::BuiltinFunction(builtin) => ("builtin", builtin.name()),
+                    };
+                    let traced = self.traced.contains(name);
+                    out.write_all(b"  {\"name\": ")?;
+                    write_json_text(out, &bytes_of(name))?;
+                    write!(out, ", \"kind\": \"{kind}\", \"body\": ")?;
+                    write_json_text(out, &bytes_of(body))?;
+                    write!(out, ", \"traced\": {traced}, \"pushdef_depth\": {depth}}}")?;
+                }
+                out.write_all(b"\n]\n")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,986 @@
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use crate::builtins::{self, Builtin};
+use crate::error::{Error, Sink};
+use crate::input::{Input, Item, Location};
+use crate::regex::Regex;
+use crate::symtab::SymbolTable;
+
+#[derive(Clone)]
+pub enum MacroValue {
+    Text(String),
+    BuiltinFunction(&'static Builtin),
+}
+
+impl MacroValue {
+    /// The text of a value; builtins have none.
+    pub(crate) fn text(&self) -> &str {
+        match self {
+            Self::Text(text) => text,
+            Self::BuiltinFunction(_) => "",
+        }
+    }
+}
+
+impl Default for MacroValue {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+pub const DEFAULT_QUOTE_START: &str = "`";
+pub const DEFAULT_QUOTE_END: &str = "'";
+
+pub const DEFAULT_COMMENT_START: &str = "#";
+pub const DEFAULT_COMMENT_END: &str = "\n";
+
+/// The current quote and comment delimiters. An empty start delimiter
+/// disables comments.
+pub struct Delimiters {
+    pub(crate) comment_start: String,
+    pub(crate) comment_end: String,
+    pub(crate) quote_start: String,
+    pub(crate) quote_end: String,
+}
+
+impl Delimiters {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            comment_start: DEFAULT_COMMENT_START.into(),
+            comment_end: DEFAULT_COMMENT_END.into(),
+            quote_start: DEFAULT_QUOTE_START.into(),
+            quote_end: DEFAULT_QUOTE_END.into(),
+        }
+    }
+
+    /// Sets the comment delimiters like `changecom`: an empty `start`
+    /// disables comments, and otherwise an empty `end` means a newline.
+    pub(crate) fn set_comments(&mut self, start: &str, end: &str) {
+        self.comment_start = start.into();
+        self.comment_end = match end {
+            _ if start.is_empty() => String::new(),
+            "" => DEFAULT_COMMENT_END.into(),
+            end => end.into(),
+        };
+    }
+
+    /// Whether comments are recognized at all.
+    pub(crate) const fn has_comments(&self) -> bool {
+        !self.comment_start.is_empty()
+    }
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The state of one m4 run: options, the symbol table, diversions and sinks.
+///
+/// Engines are created with [`EngineBuilder`](crate::EngineBuilder).
+pub struct Engine<'a> {
+    pub(crate) options: Options,
+    pub(crate) output: Sink<'a>,
+    pub(crate) debug_output: Sink<'a>,
+    pub(crate) diagnostics: Sink<'a>,
+    pub(crate) symbols: SymbolTable,
+    pub(crate) cur_diversion: i64,
+    /// The text of the positive diversions that have any, by number.
+    pub(crate) diversion_data: BTreeMap<i64, String>,
+    pub(crate) delimiters: Delimiters,
+    pub(crate) input: Input,
+    /// Set once an error was reported, so the run fails in the end.
+    pub(crate) failed: bool,
+    /// Text saved by `m4wrap` for the end of input, in the order it came.
+    pub(crate) wrapped: Vec<String>,
+    /// Recognizes macro names when `changeword` or `--word-regexp` replaced
+    /// the default scanning.
+    pub(crate) word_regexp: Option<Regex>,
+    /// Flags definitions whose bodies match, for `--warn-macro-sequence`.
+    pub(crate) macro_sequence: Option<Regex>,
+    /// The exit status of the last shell command.
+    pub(crate) sysval: i32,
+    /// The status `m4exit` asked for, once it has stopped the run.
+    pub(crate) exit_code: Option<i32>,
+    /// Names whose calls are traced, whether or not they're defined.
+    pub(crate) traced: HashSet<String>,
+    /// The debug flag letters in effect, changed at runtime by `debugmode`.
+    pub(crate) debug_flags: String,
+    /// How many macro calls are in progress, counting the one whose
+    /// arguments are being collected.
+    pub(crate) expansion_level: usize,
+    /// How much text was thrown away in the current negative diversion.
+    pub(crate) discarded: usize,
+    /// For synclines, the input line the output is on, or `None` when the
+    /// next marker must name the file too, as after a diversion or input
+    /// file change.
+    pub(crate) output_line: Option<usize>,
+    /// The input file the last syncline was for.
+    pub(crate) synced_file: String,
+    /// Whether the next output starts a line, where a syncline may go.
+    pub(crate) start_of_output_line: bool,
+}
+
+/// Options fixed at build time.
+#[allow(clippy::struct_excessive_bools)]
+pub struct Options {
+    pub(crate) program_name: String,
+    pub(crate) gnu: bool,
+    pub(crate) nesting_limit: u64,
+    pub(crate) word_regexp: Option<String>,
+    pub(crate) macro_sequence: Option<String>,
+    pub(crate) safer: bool,
+    pub(crate) extensions: bool,
+    pub(crate) shell: Option<String>,
+    pub(crate) debug_flags: String,
+    pub(crate) fatal_warnings: bool,
+    pub(crate) quiet: bool,
+    pub(crate) synclines: bool,
+    pub(crate) prefix_builtins: bool,
+    pub(crate) include_paths: Vec<PathBuf>,
+    pub(crate) traced: Vec<String>,
+}
+
+/// Reads a decimal number from a frozen file, up to and including `sep`.
+fn read_int<I: Iterator<Item = u8>>(data: &mut I, sep: u8) -> Result<i64, Error> {
+    let bad_number = || Error::FrozenSyntax("bad number".into());
+    let mut text = Vec::new();
+    loop {
+        match data.next() {
+            Some(c) if c == sep => break,
+            Some(c) => text.push(c),
+            None => return Err(bad_number()),
+        }
+    }
+    let negative = text.first() == Some(&b'-');
+    let digits = &text[usize::from(negative)..];
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return Err(bad_number());
+    }
+    digits.iter().try_fold(0_i64, |result, &c| {
+        let digit = i64::from(c - b'0');
+        let digit = if negative { -digit } else { digit };
+        result
+            .checked_mul(10)
+            .and_then(|result| result.checked_add(digit))
+            .ok_or_else(bad_number)
+    })
+}
+
+/// A macro call whose arguments are still being read.
+struct PendingCall {
+    name: String,
+    /// Where the name was read, which traces report.
+    location: Location,
+    value: MacroValue,
+    traced: bool,
+    args: Vec<MacroValue>,
+    /// The argument being read.
+    arg: String,
+    /// A builtin token read into it.
+    builtin: Option<&'static Builtin>,
+    /// How many parentheses are open in it.
+    depth: usize,
+    /// Whether only skipped whitespace has been read into it so far.
+    leading: bool,
+}
+
+impl PendingCall {
+    /// Adds the argument being read to the others and starts a new one.
+    fn end_arg(&mut self) {
+        let arg = mem::take(&mut self.arg);
+        self.args.push(arg_value(arg, self.builtin.take()));
+    }
+}
+
+/// What the scanner hands to the expansion loop.
+enum Token {
+    Word(String),
+    Other(u8),
+    Comment(String),
+    /// A quoted string, without its outer quotes.
+    Quoted(String),
+    Builtin(&'static Builtin),
+}
+
+/// An argument is a builtin only if the builtin token is all it holds.
+fn arg_value(text: String, builtin: Option<&'static Builtin>) -> MacroValue {
+    match builtin {
+        Some(builtin) if text.is_empty() => MacroValue::BuiltinFunction(builtin),
+        _ => MacroValue::Text(text),
+    }
+}
+
+/// Text holds one char per byte of input, whatever the encoding; this is
+/// the text for `bytes`.
+pub fn text_of(bytes: &[u8]) -> String {
+    bytes.iter().copied().map(char::from).collect()
+}
+
+/// The bytes a text stands for; the inverse of [`text_of`].
+pub fn bytes_of(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .collect()
+}
+
+/// The file name or command argument a text stands for. Off unix, where
+/// names aren't bytes, anything that isn't UTF-8 is replaced.
+pub fn os_string_of(text: &str) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(bytes_of(text))
+    }
+    #[cfg(not(unix))]
+    {
+        String::from_utf8_lossy(&bytes_of(text)).into_owned().into()
+    }
+}
+
+/// Reads `len` bytes of text from a frozen file.
+fn read_string<I: Iterator<Item = u8>>(data: &mut I, len: i64) -> Result<String, Error> {
+    if len < 0 {
+        return Err(Error::FrozenSyntax("negative length".into()));
+    }
+    (0..len)
+        .map(|_| {
+            data.next()
+                .map(char::from)
+                .ok_or_else(|| Error::FrozenSyntax("premature end of file".into()))
+        })
+        .collect()
+}
+
+/// The error for a frozen file `record` that doesn't end where its lengths
+/// say it does.
+fn missing_newline(record: char) -> Error {
+    Error::FrozenSyntax(format!("missing newline after {record} declaration"))
+}
+
+fn skip_comment<I: Iterator<Item = u8>>(data: &mut I, end: u8) {
+    for c in data {
+        if c == end {
+            break;
+        }
+    }
+}
+
+impl Engine<'_> {
+    pub(crate) fn print_to_diversion(&mut self, content: &str) -> Result<(), Error> {
+        match self.cur_diversion {
+            0 => self.output.write(&bytes_of(content))?,
+            number if number > 0 => self
+                .diversion_data
+                .entry(number)
+                .or_default()
+                .push_str(content),
+            _ => self.discarded += content.chars().count(),
+        }
+        Ok(())
+    }
+
+    /// Writes `text`, read on `line` of the current input file, to the
+    /// current diversion. With synclines on, output that starts a line is
+    /// first preceded by a `#line` marker if it no longer matches the
+    /// input line; text within one token is never split by a marker.
+    fn ship(&mut self, text: &str, line: usize) -> Result<(), Error> {
+        if !self.options.synclines || self.cur_diversion < 0 || text.is_empty() {
+            return self.print_to_diversion(text);
+        }
+        let file = &self.input.location().file;
+        if *file != self.synced_file {
+            self.synced_file.clone_from(file);
+            self.output_line = None;
+        }
+        if mem::take(&mut self.start_of_output_line) {
+            self.output_line = self.output_line.map(|current| current + 1);
+            if self.output_line != Some(line) {
+                let marker = match self.output_line {
+                    None if !self.synced_file.is_empty() => {
+                        format!("#line {line} \"{}\"\n", self.synced_file)
+                    }
+                    _ => format!("#line {line}\n"),
+                };
+                self.print_to_diversion(&marker)?;
+                self.output_line = Some(line);
+            }
+        }
+        for c in text.chars() {
+            if mem::take(&mut self.start_of_output_line) {
+                self.output_line = self.output_line.map(|current| current + 1);
+            }
+            self.start_of_output_line = c == '\n';
+        }
+        self.print_to_diversion(text)
+    }
+
+    /// Writes out the diversions in order and empties them, whatever the
+    /// current diversion is.
+    fn flush_diversions(&mut self) -> Result<(), Error> {
+        for text in mem::take(&mut self.diversion_data).into_values() {
+            self.output.write(&bytes_of(&text))?;
+        }
+        Ok(())
+    }
+
+    /// Reports how much text the current diversion discarded since it was
+    /// entered, if it's a negative one and the `i` debug flag is set.
+    pub(crate) fn report_discarded(&mut self) -> Result<(), Error> {
+        let discarded = mem::take(&mut self.discarded);
+        if discarded > 0 && self.debug_flags.contains('i') {
+            let line = format!(
+                "m4debug: {discarded} bytes discarded in diversion {}\n",
+                self.cur_diversion
+            );
+            self.debug_output.write(line.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Writes `text` to the diagnostics. What was output so far is flushed
+    /// first, so if both go to the same file, the text lands where it was
+    /// produced.
+    pub(crate) fn diagnose(&mut self, text: &str) -> Result<(), Error> {
+        self.output.flush()?;
+        self.diagnostics.write(&bytes_of(text))?;
+        self.diagnostics.flush()
+    }
+
+    /// Whether a warning is shown: quiet runs drop warnings altogether, so
+    /// they can't fail the run even with fatal warnings on.
+    const fn warning_shown(&mut self) -> bool {
+        if self.options.quiet {
+            return false;
+        }
+        if self.options.fatal_warnings {
+            self.failed = true;
+        }
+        true
+    }
+
+    /// Reports a problem with the input being read that doesn't stop
+    /// processing, unless the run is quiet. With fatal warnings, it makes
+    /// the run fail.
+    pub(crate) fn warn(&mut self, message: &str) -> Result<(), Error> {
+        let location = self.input.location().clone();
+        self.warn_at(&location, message)
+    }
+
+    /// Reports a problem with the input being read that makes the run
+    /// fail, without stopping it. Quiet runs report it too.
+    pub(crate) fn error(&mut self, message: &str) -> Result<(), Error> {
+        let location = self.input.location().clone();
+        self.error_at(&location, message)
+    }
+
+    /// Like [`error`](Self::error), for a problem with the input at
+    /// `location`.
+    pub(crate) fn error_at(&mut self, location: &Location, message: &str) -> Result<(), Error> {
+        self.failed = true;
+        self.diagnose(&self.located(location, message))
+    }
+
+    /// Like [`warn`](Self::warn), for a problem with the input at
+    /// `location`.
+    pub(crate) fn warn_at(&mut self, location: &Location, message: &str) -> Result<(), Error> {
+        if !self.warning_shown() {
+            return Ok(());
+        }
+        self.diagnose(&self.located(location, &format!("Warning: {message}")))
+    }
+
+    /// The diagnostic line for `message` about the input at `location`. Before
+    /// any input was read, there's no location to give.
+    fn located(&self, location: &Location, message: &str) -> String {
+        let program = &self.options.program_name;
+        match location {
+            Location { line: 0, .. } => format!("{program}: {message}\n"),
+            Location { file, line } => format!("{program}:{file}:{line}: {message}\n"),
+        }
+    }
+
+    /// The exit status the run should end with: the one given to `m4exit`,
+    /// or else 1 if an error was reported.
+    #[must_use]
+    pub const fn exit_status(&self) -> i32 {
+        match self.exit_code {
+            Some(code) if code != 0 => code,
+            _ if self.failed => 1,
+            _ => 0,
+        }
+    }
+
+    /// Opens `name` for `include`, like [`open_input`](Self::open_input).
+    pub(crate) fn open_include(&self, name: &str) -> io::Result<File> {
+        self.open_input(Path::new(&os_string_of(name)))
+    }
+
+    /// Opens an input file the way GNU m4 does, for `include` as well as
+    /// the command line: relative paths that aren't in the current
+    /// directory are looked up along the
+    /// [include path](crate::EngineBuilder::include_path), in order.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the error from opening `path` itself if no directory has
+    /// it either.
+    pub fn open_input(&self, path: &Path) -> io::Result<File> {
+        let error = match File::open(path) {
+            Ok(file) => return Ok(file),
+            Err(e) => e,
+        };
+        if path.is_relative() {
+            for dir in &self.options.include_paths {
+                if let Ok(file) = File::open(dir.join(path)) {
+                    return Ok(file);
+                }
+            }
+        }
+        Err(error)
+    }
+
+    /// Reads `file` to the end and processes it as m4 input, which
+    /// `__file__` calls `stdin`. Once `m4exit` was called, the file is left
+    /// unread.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `file` can't be read or an output sink can't be written.
+    pub fn process<F: Read>(&mut self, file: &mut F) -> Result<(), Error> {
+        self.process_named("stdin", file)
+    }
+
+    /// Like [`process`](Self::process), for the input file `name`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `file` can't be read or an output sink can't be written.
+    pub fn process_named<F: Read>(
+        &mut self,
+        name: impl AsRef<[u8]>,
+        file: &mut F,
+    ) -> Result<(), Error> {
+        if self.exit_code.is_some() {
+            return Ok(());
+        }
+        let mut data: Vec<u8> = Vec::new();
+        file.read_to_end(&mut data).map_err(Error::Read)?;
+        self.input.push_file(text_of(name.as_ref()), data);
+        self.process_text()
+    }
+
+    /// Ends the run: rescans the text saved by `m4wrap`, unless `m4exit`
+    /// threw it away, then writes out the diversions in order and flushes
+    /// the output, debug output and diagnostics.
+    ///
+    /// # Errors
+    ///
+    /// Fails if an output sink can't be written.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        // Text wrapped while a round is rescanned forms the next round.
+        // Within a round GNU m4 rereads the last text saved first, where
+        // POSIX wants the first; the input is a stack, so pushing in the
+        // order saved gives the former.
+        while !self.wrapped.is_empty() {
+            let mut round = mem::take(&mut self.wrapped);
+            if !self.options.gnu {
+                round.reverse();
+            }
+            for text in round {
+                self.input.push(bytes_of(&text));
+            }
+            self.process_text()?;
+        }
+        self.report_discarded()?;
+        self.flush_diversions()?;
+        self.output.flush()?;
+        self.debug_output.flush()?;
+        self.diagnostics.flush()
+    }
+
+    /// Starts expanding the macro `name` if it's defined: calls it at once
+    /// if no parenthesis follows, and otherwise returns the call, whose
+    /// arguments the caller goes on to read. Returns `None` along with
+    /// whether there was anything to expand.
+    fn start_call(&mut self, name: &str) -> Result<(Option<PendingCall>, bool), Error> {
+        let Some(value) = self.symbols.lookup(name).cloned() else {
+            return Ok((None, false));
+        };
+        let with_args = self.input.peek() == Some(b'(');
+        if let MacroValue::BuiltinFunction(builtin) = &value {
+            if !with_args && builtin.is_blind() {
+                return Ok((None, false));
+            }
+        }
+        self.expansion_level += 1;
+        let limit = self.options.nesting_limit;
+        if limit != 0 && self.expansion_level as u64 > limit {
+            return Err(Error::NestingLimit(limit));
+        }
+        let call = PendingCall {
+            name: name.into(),
+            location: self.input.location().clone(),
+            value,
+            traced: self.is_traced(name),
+            args: Vec::new(),
+            arg: String::new(),
+            builtin: None,
+            depth: 0,
+            leading: true,
+        };
+        if with_args {
+            self.input.next(); // The opening parenthesis
+            Ok((Some(call), true))
+        } else {
+            self.finish_call(&call)?;
+            Ok((None, true))
+        }
+    }
+
+    /// Calls a macro once all its arguments are read.
+    fn finish_call(&mut self, call: &PendingCall) -> Result<(), Error> {
+        // `m4exit` in the arguments ends everything, this call too
+        if self.exit_code.is_none() {
+            let mark = self.input.depth();
+            self.call_macro(&call.name, &call.value, &call.args)?;
+            if call.traced {
+                self.trace_call(&call.name, &call.location, &call.args, mark)?;
+            }
+        }
+        self.expansion_level -= 1;
+        Ok(())
+    }
+
+    /// Calls the macro `name`, defined as `value`, with `args`.
+    pub(crate) fn call_macro(
+        &mut self,
+        name: &str,
+        value: &MacroValue,
+        args: &[MacroValue],
+    ) -> Result<(), Error> {
+        match value {
+            // The expansion is rescanned as if it had been in the input
+            MacroValue::Text(body) => {
+                let expansion = self.substitute_args(name, body, args)?;
+                self.input.push(bytes_of(&expansion));
+            }
+            MacroValue::BuiltinFunction(builtin) => self.call_builtin(builtin, args)?,
+        }
+        Ok(())
+    }
+
+    /// The body of a text macro with its argument references replaced:
+    /// `$0` is the name, `$1` and up the arguments, `$#` their number, and
+    /// `$*` and `$@` all of them joined by commas, the latter each quoted. A
+    /// `$` followed by anything else is kept.
+    ///
+    /// Like GNU m4, `$10` is the tenth argument, every digit counting, but
+    /// only one digit is read in traditional mode, where it's `$1` followed
+    /// by `0`; that reading is warned about. `${10}` always means the tenth
+    /// argument with GNU extensions, and is plain text without them.
+    fn substitute_args(
+        &mut self,
+        name: &str,
+        body: &str,
+        args: &[MacroValue],
+    ) -> Result<String, Error> {
+        let mut expansion = String::new();
+        let mut chars = body.char_indices().peekable();
+        while let Some((_, c)) = chars.next() {
+            if c != '$' {
+                expansion.push(c);
+                continue;
+            }
+            let mut digits = String::new();
+            match chars.peek().copied() {
+                Some((_, '0'..='9')) => {
+                    digits.extend(chars.next().map(|(_, digit)| digit));
+                    if self.options.gnu {
+                        while let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                            digits.push(digit);
+                        }
+                    } else if let Some((_, next)) = chars.peek().filter(|(_, c)| c.is_ascii_digit())
+                    {
+                        self.warn(&format!(
+                            "`${digits}{next}' in the body of `{name}' is `${digits}' followed by `{next}'"
+                        ))?;
+                    }
+                }
+                Some((open, '{')) if self.options.gnu => {
+                    let rest = &body[open + 1..];
+                    let number = &rest[..rest.bytes().take_while(u8::is_ascii_digit).count()];
+                    if !number.is_empty() && rest[number.len()..].starts_with('}') {
+                        digits.push_str(number);
+                        chars.nth(number.len() + 1);
+                    } else {
+                        expansion.push('$');
+                    }
+                }
+                Some((_, '#')) => {
+                    chars.next();
+                    expansion.push_str(&args.len().to_string());
+                }
+                Some((_, join @ ('*' | '@'))) => {
+                    chars.next();
+                    let joined: Vec<String> = args
+                        .iter()
+                        .map(|arg| match join {
+                            '@' => self.quote(arg.text()),
+                            _ => arg.text().into(),
+                        })
+                        .collect();
+                    expansion.push_str(&joined.join(","));
+                }
+                _ => expansion.push('$'),
+            }
+            if !digits.is_empty() {
+                // Too big to be an argument is as good as missing
+                match digits.parse::<usize>() {
+                    Ok(0) => expansion.push_str(name),
+                    Ok(n) => expansion.push_str(args.get(n - 1).map_or("", MacroValue::text)),
+                    Err(_) => {}
+                }
+            }
+        }
+        Ok(expansion)
+    }
+
+    /// Whether a comment starts at the next byte of input.
+    fn at_comment_start(&self) -> bool {
+        self.delimiters.has_comments()
+            && self
+                .input
+                .looking_at(&bytes_of(&self.delimiters.comment_start))
+    }
+
+    /// Reads a whole comment, delimiters included.
+    ///
+    /// A comment left open at the end of input is an error, reported where
+    /// it started; it's kept as far as it goes.
+    fn read_comment(&mut self) -> Result<String, Error> {
+        let start = self.input.location().clone();
+        self.input
+            .skip(bytes_of(&self.delimiters.comment_start).len());
+        let mut body = String::new();
+        loop {
+            match self.input.next_item() {
+                Some(Item::Byte(c)) => body.push(c as char),
+                // Builtin tokens lose their meaning in comments
+                Some(Item::Builtin(_)) => continue,
+                None => {
+                    self.error_at(&start, "end of file in comment")?;
+                    break;
+                }
+            }
+            if body.ends_with(self.delimiters.comment_end.as_str()) {
+                break;
+            }
+        }
+        Ok(format!("{}{body}", self.delimiters.comment_start))
+    }
+
+    /// Whether a quoted string starts at the next byte of input.
+    fn at_quote_start(&self) -> bool {
+        !self.delimiters.quote_start.is_empty()
+            && self
+                .input
+                .looking_at(&bytes_of(&self.delimiters.quote_start))
+    }
+
+    /// Reads a quoted string, returning its text without the outer quotes.
+    /// Nested quotes are kept; the string only ends when they all close.
+    ///
+    /// A string left open at the end of input is an error, reported where
+    /// it started; its text is kept as far as it goes.
+    fn read_quoted(&mut self) -> Result<String, Error> {
+        let start = self.input.location().clone();
+        self.input
+            .skip(bytes_of(&self.delimiters.quote_start).len());
+        let mut text = String::new();
+        let mut depth = 1usize;
+        let quote_end = bytes_of(&self.delimiters.quote_end);
+        loop {
+            // The end quote is checked first, so equal quotes don't nest
+            let delimiter = if self.input.looking_at(&quote_end) {
+                depth -= 1;
+                if depth == 0 {
+                    self.input.skip(quote_end.len());
+                    return Ok(text);
+                }
+                &self.delimiters.quote_end
+            } else if self.at_quote_start() {
+                depth += 1;
+                &self.delimiters.quote_start
+            } else {
+                match self.input.next_item() {
+                    Some(Item::Byte(c)) => text.push(c as char),
+                    // Builtin tokens lose their meaning in quotes
+                    Some(Item::Builtin(_)) => {}
+                    None => {
+                        self.error_at(&start, "end of file in string")?;
+                        return Ok(text);
+                    }
+                }
+                continue;
+            };
+            text.push_str(delimiter);
+            self.input.skip(delimiter.chars().count());
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, Error> {
+        if self.at_comment_start() {
+            return Ok(Some(Token::Comment(self.read_comment()?)));
+        }
+        if self.at_quote_start() {
+            return Ok(Some(Token::Quoted(self.read_quoted()?)));
+        }
+        let words_by_regexp = self.word_regexp.is_some();
+        if words_by_regexp {
+            if let Some(word) = self.read_regexp_word() {
+                return Ok(Some(Token::Word(word)));
+            }
+        }
+        let c = match self.input.next_item() {
+            Some(Item::Byte(c)) => c,
+            Some(Item::Builtin(builtin)) => return Ok(Some(Token::Builtin(builtin))),
+            None => return Ok(None),
+        };
+        Ok(Some(match c {
+            _ if words_by_regexp => Token::Other(c),
+            b'_' | b'A'..=b'Z' | b'a'..=b'z' => {
+                // Like GNU m4, a name is read whole even if a comment or
+                // quote delimiter starts inside it
+                let mut name = String::from(c as char);
+                while let Some(c) = self
+                    .input
+                    .peek()
+                    .filter(|&c| c == b'_' || c.is_ascii_alphanumeric())
+                {
+                    self.input.next();
+                    name.push(c as char);
+                }
+                Token::Word(name)
+            }
+            _ => Token::Other(c),
+        }))
+    }
+
+    /// Reads the longest word the word regexp matches in full, if the next
+    /// byte can start one.
+    fn read_regexp_word(&mut self) -> Option<String> {
+        let regexp = self.word_regexp.as_ref()?;
+        let mut word = Vec::new();
+        while let Some(c) = self.input.peek() {
+            word.push(c);
+            let matched = regexp.match_at(&word, 0);
+            if matched.and_then(|captures| captures[0]) != Some((0, word.len())) {
+                word.pop();
+                break;
+            }
+            self.input.next();
+        }
+        if word.is_empty() {
+            None
+        } else {
+            Some(text_of(&word))
+        }
+    }
+
+    /// The name a word is looked up under: with a word regexp that has a
+    /// group, only the text the first group matched.
+    fn macro_name<'w>(&self, word: &'w str) -> &'w str {
+        self.word_regexp
+            .as_ref()
+            .filter(|regexp| regexp.groups() > 0)
+            .and_then(|regexp| regexp.match_at(&bytes_of(word), 0)?[1])
+            .map_or(word, |(start, end)| {
+                let offset = |n| word.char_indices().nth(n).map_or(word.len(), |(i, _)| i);
+                &word[offset(start)..offset(end)]
+            })
+    }
+
+    /// Scans the pending input, expanding macros as it goes, until none is
+    /// left.
+    ///
+    /// Calls whose arguments are being read wait on a stack, innermost last,
+    /// rather than in nested Rust calls, so only the nesting limit bounds
+    /// how deeply they nest. An argument list is split on commas that
+    /// aren't quoted or nested inside inner parentheses, and unquoted
+    /// whitespace before each argument is skipped. A call still open at the
+    /// end of input is an error, reported where it started, but it's made
+    /// with the arguments read so far; arguments never continue into the
+    /// next input file. Calls left open by `m4exit` are dropped silently.
+    fn process_text(&mut self) -> Result<(), Error> {
+        let mut calls: Vec<PendingCall> = Vec::new();
+        loop {
+            let line = self.input.location().line;
+            let Some(token) = self.next_token()? else {
+                // `m4exit` threw the input away, pending calls and all
+                if self.exit_code.is_some() {
+                    return Ok(());
+                }
+                let Some(mut call) = calls.pop() else {
+                    return Ok(());
+                };
+                self.error_at(&call.location, "end of file in argument list")?;
+                call.end_arg();
+                self.finish_call(&call)?;
+                continue;
+            };
+            if let Some(call) = calls.last_mut() {
+                if call.leading {
+                    if matches!(token, Token::Other(b' ' | b'\t' | b'\r' | b'\n')) {
+                        continue;
+                    }
+                    call.leading = false;
+                }
+            }
+            // Commas and parentheses only split arguments outside of inner
+            // parentheses
+            let top_level = calls.last().is_some_and(|call| call.depth == 0);
+            match token {
+                Token::Word(word) => {
+                    let name = self.macro_name(&word).to_owned();
+                    match self.start_call(&name)? {
+                        (Some(call), _) => calls.push(call),
+                        (None, true) => {}
+                        (None, false) => self.emit(&mut calls, &word, line)?,
+                    }
+                }
+                Token::Other(b')') if top_level => {
+                    if let Some(mut call) = calls.pop() {
+                        call.end_arg();
+                        self.finish_call(&call)?;
+                    }
+                }
+                Token::Other(b',') if top_level => {
+                    if let Some(call) = calls.last_mut() {
+                        call.end_arg();
+                        call.leading = true;
+                    }
+                }
+                Token::Other(c) => {
+                    if let Some(call) = calls.last_mut() {
+                        match c {
+                            b'(' => call.depth += 1,
+                            b')' => call.depth -= 1,
+                            _ => {}
+                        }
+                    }
+                    self.emit(&mut calls, &(c as char).to_string(), line)?;
+                }
+                Token::Comment(text) | Token::Quoted(text) => self.emit(&mut calls, &text, line)?,
+                Token::Builtin(builtin) => {
+                    // A builtin token outside of an argument has no text
+                    if let Some(call) = calls.last_mut() {
+                        call.builtin = Some(builtin);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds `text` to the argument being read, or outside of any call,
+    /// writes it to the current diversion as read on `line`.
+    fn emit(&mut self, calls: &mut [PendingCall], text: &str, line: usize) -> Result<(), Error> {
+        match calls.last_mut() {
+            Some(call) => {
+                call.arg.push_str(text);
+                Ok(())
+            }
+            None => self.ship(text, line),
+        }
+    }
+
+    /// Reads a frozen state file, as written by GNU m4's `--freeze-state`.
+    /// Like GNU m4, the frozen file replaces the whole symbol table, builtins
+    /// included.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `file` can't be read or isn't a well-formed frozen file, or
+    /// if an output sink can't be written.
+    #[allow(clippy::too_many_lines)]
+    pub fn reload_state<F: Read>(&mut self, file: &mut F) -> Result<(), Error> {
+        let mut data: Vec<u8> = Vec::new();
+        file.read_to_end(&mut data).map_err(Error::Read)?;
+        self.symbols.clear();
+        let mut data = data.iter().copied();
+        while let Some(c) = data.next() {
+            // Frozen files always use `#` comments, whatever they set for m4
+            if c == b'#' {
+                skip_comment(&mut data, b'\n');
+            } else if c == b'C' {
+                let start_len = read_int(&mut data, b',')?;
+                let end_len = read_int(&mut data, b'\n')?;
+                self.delimiters.comment_start = read_string(&mut data, start_len)?;
+                self.delimiters.comment_end = read_string(&mut data, end_len)?;
+                if data.next() != Some(b'\n') {
+                    return Err(missing_newline('C'));
+                }
+            } else if c == b'D' {
+                let div_num = read_int(&mut data, b',')?;
+                let content_len = read_int(&mut data, b'\n')?;
+                let content = read_string(&mut data, content_len)?;
+                self.cur_diversion = div_num;
+                self.print_to_diversion(&content)?;
+                if data.next() != Some(b'\n') {
+                    return Err(missing_newline('D'));
+                }
+            } else if c == b'F' {
+                let name_len = read_int(&mut data, b',')?;
+                let value_len = read_int(&mut data, b'\n')?;
+                let name = read_string(&mut data, name_len)?;
+                let value = read_string(&mut data, value_len)?;
+                // A builtin this engine lacks, say in safer mode, stays out
+                if let Some(builtin) = builtins::find_builtin(&self.options, &value) {
+                    self.symbols
+                        .pushdef(&name, MacroValue::BuiltinFunction(builtin));
+                } else {
+                    self.warn(&format!("undefined builtin `{value}'"))?;
+                }
+                if data.next() != Some(b'\n') {
+                    return Err(missing_newline('F'));
+                }
+            } else if c == b'Q' {
+                let start_len = read_int(&mut data, b',')?;
+                let end_len = read_int(&mut data, b'\n')?;
+                self.delimiters.quote_start = read_string(&mut data, start_len)?;
+                self.delimiters.quote_end = read_string(&mut data, end_len)?;
+                if data.next() != Some(b'\n') {
+                    return Err(missing_newline('Q'));
+                }
+            } else if c == b'T' {
+                let name_len = read_int(&mut data, b',')?;
+                let value_len = read_int(&mut data, b'\n')?;
+                let name = read_string(&mut data, name_len)?;
+                let value = read_string(&mut data, value_len)?;
+                self.symbols.pushdef(&name, MacroValue::Text(value));
+                if data.next() != Some(b'\n') {
+                    return Err(missing_newline('T'));
+                }
+            } else if c == b'V' {
+                if data.next() != Some(b'1') {
+                    return Err(Error::FrozenSyntax(
+                        "incorrect/missing version in V declaration".into(),
+                    ));
+                }
+                if data.next() != Some(b'\n') {
+                    return Err(missing_newline('V'));
+                }
+            } else {
+                self.output.write(&[c])?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use std::error;
+use std::fmt;
+use std::io::{self, Write};
+
+/// The output streams an [`Engine`](crate::Engine) writes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    /// Expanded text, normally stdout.
+    Output,
+    /// Debug and trace output, normally stderr.
+    Debug,
+    /// Warnings and error messages, normally stderr.
+    Diagnostics,
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Output => "output",
+            Self::Debug => "debug output",
+            Self::Diagnostics => "diagnostics",
+        })
+    }
+}
+
+/// Errors that stop an [`Engine`](crate::Engine) from processing further.
+#[derive(Debug)]
+pub enum Error {
+    /// Writing to one of the engine's sinks failed. The sink is disabled
+    /// afterwards, so the failure is only reported once.
+    Write { stream: Stream, source: io::Error },
+    /// Reading input failed.
+    Read(io::Error),
+    /// Macro calls nested deeper than the
+    /// [nesting limit](crate::EngineBuilder::nesting_limit) allows.
+    NestingLimit(u64),
+    /// A file given to [`reload_state`](crate::Engine::reload_state) isn't
+    /// a well-formed frozen file.
+    FrozenSyntax(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Write { stream, source } => write!(f, "error writing {stream}: {source}"),
+            Self::Read(source) => write!(f, "error reading input: {source}"),
+            Self::NestingLimit(limit) => write!(
+                f,
+                "recursion limit of {limit} exceeded, use -L<N> to change it"
+            ),
+            Self::FrozenSyntax(reason) => {
+                write!(f, "syntax error in reload state file: {reason}")
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Write { source, .. } | Self::Read(source) => Some(source),
+            Self::NestingLimit(_) | Self::FrozenSyntax(_) => None,
+        }
+    }
+}
+
+/// What went wrong in `error`, described the way C's `strerror` would,
+/// without the error code Rust's `Display` adds.
+pub fn os_reason(error: &io::Error) -> String {
+    let mut text = error.to_string();
+    if let Some(code) = error.raw_os_error() {
+        let suffix = format!(" (os error {code})");
+        if text.ends_with(&suffix) {
+            text.truncate(text.len() - suffix.len());
+        }
+    }
+    text
+}
+
+/// A writer that turns its first failure into an [`Error`] and then goes
+/// quiet, so a full disk or closed pipe isn't reported once per write.
+pub struct Sink<'a> {
+    inner: Box<dyn Write + 'a>,
+    stream: Stream,
+    failed: bool,
+}
+
+impl<'a> Sink<'a> {
+    pub fn new(inner: Box<dyn Write + 'a>, stream: Stream) -> Self {
+        Self {
+            inner,
+            stream,
+            failed: false,
+        }
+    }
+
+    fn check(&mut self, result: io::Result<()>) -> Result<(), Error> {
+        result.map_err(|source| {
+            self.failed = true;
+            Error::Write {
+                stream: self.stream,
+                source,
+            }
+        })
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.failed {
+            return Ok(());
+        }
+        let result = self.inner.write_all(data);
+        self.check(result)
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if self.failed {
+            return Ok(());
+        }
+        let result = self.inner.flush();
+        self.check(result)
+    }
+}
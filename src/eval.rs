@@ -0,0 +1,308 @@
+//! Expressions for the `eval` builtin: C-like integer arithmetic on 32-bit
+//! signed numbers that wrap around, as in GNU m4.
+
+use std::fmt;
+
+/// Why an expression couldn't be evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalError {
+    /// An operand or operator is missing or out of place.
+    Syntax,
+    /// A `(` was never closed.
+    MissingRight,
+    /// A character that can't start any token.
+    BadInput,
+    /// A complete expression is followed by more text.
+    ExcessInput,
+    /// An operator m4 doesn't have, like `=`.
+    InvalidOperator,
+    DivideByZero,
+    ModuloByZero,
+    NegativeExponent,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Syntax => "bad expression in eval",
+            Self::MissingRight => "bad expression in eval (missing right parenthesis)",
+            Self::BadInput => "bad expression in eval (bad input)",
+            Self::ExcessInput => "bad expression in eval (excess input)",
+            Self::InvalidOperator => "invalid operator in eval",
+            Self::DivideByZero => "divide by zero in eval",
+            Self::ModuloByZero => "modulo by zero in eval",
+            Self::NegativeExponent => "negative exponent in eval",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Number(i32),
+    Op(&'static str),
+    Open,
+    Close,
+    End,
+}
+
+/// Operators, longest first so `**` isn't read as two `*`.
+const OPERATORS: &[&str] = &[
+    "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*", "/", "%", "+", "-", "<", ">", "=",
+    "&", "|", "^", "~", "!",
+];
+
+/// Binary operators from the loosest binding to the tightest, above the
+/// right-associative `**`.
+const LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!=", "="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+struct Parser<'e> {
+    text: &'e [u8],
+    pos: usize,
+    token: Token,
+    /// Nonzero inside the unevaluated side of `&&` or `||`, where division
+    /// by zero isn't an error.
+    skipping: usize,
+}
+
+impl Parser<'_> {
+    fn advance(&mut self) -> Result<(), EvalError> {
+        while self.text.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+        let rest = &self.text[self.pos..];
+        self.token = match rest.first() {
+            None => Token::End,
+            Some(b'(') => {
+                self.pos += 1;
+                Token::Open
+            }
+            Some(b')') => {
+                self.pos += 1;
+                Token::Close
+            }
+            Some(c) if c.is_ascii_digit() => Token::Number(self.number()?),
+            Some(_) => {
+                let op = OPERATORS
+                    .iter()
+                    .find(|op| rest.starts_with(op.as_bytes()))
+                    .ok_or(EvalError::BadInput)?;
+                self.pos += op.len();
+                Token::Op(op)
+            }
+        };
+        Ok(())
+    }
+
+    /// Reads a literal: decimal, `0` octal, `0x` hexadecimal, `0b` binary,
+    /// or `0rBASE:digits` in any base from 1 to 36. Letters are digits from
+    /// 10 up in either case, and the literal ends at the first character
+    /// that isn't a digit in its base.
+    fn number(&mut self) -> Result<i32, EvalError> {
+        let mut radix = 10;
+        if self.text[self.pos] == b'0' {
+            self.pos += 1;
+            radix = match self.text.get(self.pos).map(u8::to_ascii_lowercase) {
+                Some(b'x') => 16,
+                Some(b'b') => 2,
+                Some(b'r') => {
+                    self.pos += 1;
+                    let mut radix = 0u32;
+                    while let Some(c) = self.text.get(self.pos).filter(|c| c.is_ascii_digit()) {
+                        if radix > 36 {
+                            break;
+                        }
+                        radix = radix * 10 + u32::from(c - b'0');
+                        self.pos += 1;
+                    }
+                    if radix == 0 || radix > 36 || self.text.get(self.pos) != Some(&b':') {
+                        return Err(EvalError::BadInput);
+                    }
+                    radix
+                }
+                _ => 8,
+            };
+            if radix != 8 {
+                self.pos += 1;
+            }
+        }
+        let mut value = 0i32;
+        while let Some(digit) = self
+            .text
+            .get(self.pos)
+            .and_then(|&c| char::from(c).to_digit(36))
+        {
+            if radix == 1 {
+                // Tally marks, possibly after leading zeros
+                match digit {
+                    1 => value = value.wrapping_add(1),
+                    0 if value == 0 => {}
+                    _ => break,
+                }
+            } else if digit < radix {
+                value = value
+                    .wrapping_mul(radix.cast_signed())
+                    .wrapping_add(digit.cast_signed());
+            } else {
+                break;
+            }
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i32, EvalError> {
+        let Some(ops) = LEVELS.get(level) else {
+            return self.power();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Token::Op(op) = self.token {
+            if !ops.contains(&op) {
+                break;
+            }
+            self.advance()?;
+            // The right side of a decided `&&` or `||` is parsed, not run
+            let skip = (op == "&&" && left == 0) || (op == "||" && left != 0);
+            if skip {
+                self.skipping += 1;
+            }
+            let right = self.binary(level + 1);
+            if skip {
+                self.skipping -= 1;
+            }
+            left = self.apply(op, left, right?)?;
+        }
+        Ok(left)
+    }
+
+    fn apply(&self, op: &str, left: i32, right: i32) -> Result<i32, EvalError> {
+        let zero_error = match op {
+            "/" => Some(EvalError::DivideByZero),
+            "%" => Some(EvalError::ModuloByZero),
+            _ => None,
+        };
+        if let (Some(error), 0) = (zero_error, right) {
+            return if self.skipping > 0 { Ok(0) } else { Err(error) };
+        }
+        Ok(match op {
+            "||" => i32::from(left != 0 || right != 0),
+            "&&" => i32::from(left != 0 && right != 0),
+            "|" => left | right,
+            "^" => left ^ right,
+            "&" => left & right,
+            "==" => i32::from(left == right),
+            "!=" => i32::from(left != right),
+            "<" => i32::from(left < right),
+            "<=" => i32::from(left <= right),
+            ">" => i32::from(left > right),
+            ">=" => i32::from(left >= right),
+            // Shift counts only use their low five bits, like on most CPUs
+            "<<" => left.wrapping_shl(right.cast_unsigned()),
+            ">>" => left.wrapping_shr(right.cast_unsigned()),
+            "+" => left.wrapping_add(right),
+            "-" => left.wrapping_sub(right),
+            "*" => left.wrapping_mul(right),
+            "/" => left.wrapping_div(right),
+            "%" => left.wrapping_rem(right),
+            _ => return Err(EvalError::InvalidOperator),
+        })
+    }
+
+    /// `**`, which binds tighter than the other binary operators and groups
+    /// to the right.
+    fn power(&mut self) -> Result<i32, EvalError> {
+        let base = self.unary()?;
+        if self.token != Token::Op("**") {
+            return Ok(base);
+        }
+        self.advance()?;
+        let exponent = self.power()?;
+        let Ok(exponent) = u32::try_from(exponent) else {
+            return if self.skipping > 0 {
+                Ok(0)
+            } else {
+                Err(EvalError::NegativeExponent)
+            };
+        };
+        Ok(base.wrapping_pow(exponent))
+    }
+
+    fn unary(&mut self) -> Result<i32, EvalError> {
+        match self.token {
+            Token::Op(op @ ("+" | "-" | "~" | "!")) => {
+                self.advance()?;
+                let value = self.unary()?;
+                Ok(match op {
+                    "+" => value,
+                    "-" => value.wrapping_neg(),
+                    "~" => !value,
+                    _ => i32::from(value == 0),
+                })
+            }
+            Token::Number(value) => {
+                self.advance()?;
+                Ok(value)
+            }
+            Token::Open => {
+                self.advance()?;
+                let value = self.binary(0)?;
+                if self.token != Token::Close {
+                    return Err(EvalError::MissingRight);
+                }
+                self.advance()?;
+                Ok(value)
+            }
+            Token::Op("=") => Err(EvalError::InvalidOperator),
+            Token::Op(_) | Token::Close | Token::End => Err(EvalError::Syntax),
+        }
+    }
+}
+
+/// Evaluates `expression`.
+pub fn evaluate(expression: &str) -> Result<i32, EvalError> {
+    let mut parser = Parser {
+        text: expression.as_bytes(),
+        pos: 0,
+        token: Token::End,
+        skipping: 0,
+    };
+    parser.advance()?;
+    let value = parser.binary(0)?;
+    if parser.token == Token::End {
+        Ok(value)
+    } else {
+        Err(EvalError::ExcessInput)
+    }
+}
+
+/// Writes `value` in `radix`, from 1 (tally marks) to 36, with at least
+/// `width` digits after any sign.
+pub fn render(value: i32, radix: u32, width: usize) -> String {
+    let mut magnitude = value.unsigned_abs();
+    let mut digits = Vec::new();
+    if radix == 1 {
+        digits.resize(magnitude as usize, '1');
+    } else {
+        loop {
+            digits.push(char::from_digit(magnitude % radix, radix).unwrap_or('?'));
+            magnitude /= radix;
+            if magnitude == 0 {
+                break;
+            }
+        }
+    }
+    let mut text = String::from(if value < 0 { "-" } else { "" });
+    text.extend(std::iter::repeat_n('0', width.saturating_sub(digits.len())));
+    text.extend(digits.iter().rev());
+    text
+}
@@ -0,0 +1,529 @@
+//! The `format` builtin: printf-style formatting, following GNU m4.
+
+use crate::engine::{Engine, MacroValue};
+use crate::error::Error;
+
+/// Conversions a spec may end in, before its flags and size rule some out.
+const CONVERSIONS: &str = "aAcdeEfFgGiosuxX";
+
+/// The integer type a conversion works on, as set by its size modifier.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Size {
+    Char,
+    Short,
+    Int,
+    Long,
+}
+
+/// One `%` conversion, minus the conversion character.
+#[allow(clippy::struct_excessive_bools)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+    size: Size,
+}
+
+impl Spec {
+    /// Pads `body` (which starts with `prefix`, a sign or radix marker) out
+    /// to the field width. Zero padding goes between the prefix and the rest.
+    fn pad(&self, prefix: &str, body: &str, zero: bool) -> String {
+        let len = prefix.chars().count() + body.chars().count();
+        let fill = self.width.saturating_sub(len);
+        if self.left {
+            format!("{prefix}{body}{}", " ".repeat(fill))
+        } else if zero {
+            format!("{prefix}{}{body}", "0".repeat(fill))
+        } else {
+            format!("{}{prefix}{body}", " ".repeat(fill))
+        }
+    }
+
+    const fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    /// Applies the precision, the minimum number of digits, to `digits`.
+    fn min_digits(&self, digits: String) -> String {
+        match self.precision {
+            Some(0) if digits == "0" => String::new(),
+            Some(precision) if digits.len() < precision => {
+                format!("{}{digits}", "0".repeat(precision - digits.len()))
+            }
+            _ => digits,
+        }
+    }
+
+    /// Formats a signed conversion (`d`, `i`). Narrower sizes wrap, as the
+    /// C conversion would.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn signed(&self, value: i64) -> String {
+        let value = match self.size {
+            Size::Char => i64::from(truncate(value) as i8),
+            Size::Short => i64::from(truncate(value) as i16),
+            Size::Int => i64::from(truncate(value) as i32),
+            Size::Long => value,
+        };
+        let digits = self.min_digits(value.unsigned_abs().to_string());
+        let zero = self.zero && self.precision.is_none();
+        self.pad(self.sign(value < 0), &digits, zero)
+    }
+
+    /// Formats an unsigned conversion (`o`, `u`, `x`, `X`).
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn unsigned(&self, value: i64, conversion: char) -> String {
+        let value = match self.size {
+            Size::Char => u64::from(truncate(value) as u8),
+            Size::Short => u64::from(truncate(value) as u16),
+            Size::Int => u64::from(truncate(value)),
+            Size::Long => value as u64,
+        };
+        let digits = match conversion {
+            'o' => format!("{value:o}"),
+            'x' => format!("{value:x}"),
+            'X' => format!("{value:X}"),
+            _ => value.to_string(),
+        };
+        let mut digits = self.min_digits(digits);
+        let mut prefix = "";
+        if self.alternate {
+            match conversion {
+                'o' if !digits.starts_with('0') => digits.insert(0, '0'),
+                'x' if value != 0 => prefix = "0x",
+                'X' if value != 0 => prefix = "0X",
+                _ => {}
+            }
+        }
+        let zero = self.zero && self.precision.is_none();
+        self.pad(prefix, &digits, zero)
+    }
+
+    /// Formats a floating-point conversion (`a`, `e`, `f`, `g` and
+    /// capitals).
+    fn float(&self, value: f64, conversion: char) -> String {
+        let upper = conversion.is_ascii_uppercase();
+        let sign = self.sign(value.is_sign_negative());
+        if !value.is_finite() {
+            let body = if value.is_nan() { "nan" } else { "inf" };
+            let body = if upper {
+                body.to_ascii_uppercase()
+            } else {
+                body.into()
+            };
+            return self.pad(sign, &body, false);
+        }
+        let value = value.abs();
+        if conversion.eq_ignore_ascii_case(&'a') {
+            // Zero padding goes after the `0x`
+            let prefix = format!("{sign}0x");
+            let body = hexadecimal(value, self.precision, self.alternate);
+            let padded = self.pad(&prefix, &body, self.zero);
+            return if upper {
+                padded.to_ascii_uppercase()
+            } else {
+                padded
+            };
+        }
+        let precision = self.precision.unwrap_or(6);
+        let body = match conversion.to_ascii_lowercase() {
+            'e' => exponential(value, precision, self.alternate),
+            'f' => fixed(value, precision, self.alternate),
+            _ => general(value, precision, self.alternate),
+        };
+        let body = if upper {
+            body.to_ascii_uppercase()
+        } else {
+            body
+        };
+        self.pad(sign, &body, self.zero)
+    }
+}
+
+/// The low 32 bits of `value`, which the narrower C types are cut from.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+const fn truncate(value: i64) -> u32 {
+    value as u32
+}
+
+/// `%f` without sign: `precision` digits after the point.
+fn fixed(value: f64, precision: usize, alternate: bool) -> String {
+    let mut body = format!("{value:.precision$}");
+    if alternate && precision == 0 {
+        body.push('.');
+    }
+    body
+}
+
+/// `%e` without sign: one digit, `precision` decimals and a signed exponent
+/// of at least two digits.
+fn exponential(value: f64, precision: usize, alternate: bool) -> String {
+    let formatted = format!("{value:.precision$e}");
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let point = if alternate && precision == 0 { "." } else { "" };
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{mantissa}{point}e{sign}{:02}", exponent.unsigned_abs())
+}
+
+/// `%g` without sign: `%e` or `%f`, whichever C picks for the exponent,
+/// with trailing zeros dropped unless `alternate`.
+fn general(value: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    let formatted = format!("{value:.*e}", precision - 1);
+    let exponent: i64 = formatted
+        .split_once('e')
+        .and_then(|(_, exponent)| exponent.parse().ok())
+        .unwrap_or(0);
+    let digits = i64::try_from(precision).unwrap_or(i64::MAX);
+    let mut body = if exponent < -4 || exponent >= digits {
+        exponential(value, precision - 1, alternate)
+    } else {
+        let decimals = usize::try_from(digits - 1 - exponent).unwrap_or(0);
+        fixed(value, decimals, alternate)
+    };
+    if !alternate {
+        let (number, exponent) = body
+            .find('e')
+            .map_or((body.as_str(), ""), |at| body.split_at(at));
+        if number.contains('.') {
+            let number = number.trim_end_matches('0').trim_end_matches('.');
+            body = format!("{number}{exponent}");
+        }
+    }
+    body
+}
+
+/// `%a` without sign or `0x`: the leading bit, the rest of the mantissa in
+/// hexadecimal and a binary exponent, as glibc writes it. Without a
+/// precision the mantissa is exact with trailing zeros dropped; otherwise
+/// it's rounded to that many digits, ties to even.
+fn hexadecimal(value: f64, precision: Option<usize>, alternate: bool) -> String {
+    const FRACTION_DIGITS: usize = 13;
+    let bits = value.to_bits();
+    let biased = (bits >> 52) & 0x7ff;
+    let mut fraction = bits & ((1 << 52) - 1);
+    let (mut lead, exponent) = match (biased, fraction) {
+        (0, 0) => (0, 0),
+        (0, _) => (0, -1022),
+        _ => (1, i64::try_from(biased).unwrap_or(0) - 1023),
+    };
+    let digits = precision.map_or(FRACTION_DIGITS, |p| p.min(FRACTION_DIGITS));
+    if digits < FRACTION_DIGITS {
+        let shift = (FRACTION_DIGITS - digits) * 4;
+        let rest = fraction & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        fraction >>= shift;
+        // With no digits left, the leading one decides a tie
+        let odd = if digits == 0 { lead } else { fraction } & 1 == 1;
+        if rest > half || (rest == half && odd) {
+            fraction += 1;
+            if fraction >> (digits * 4) != 0 {
+                lead += 1;
+                fraction = 0;
+            }
+        }
+    }
+    let mut hex = if digits == 0 {
+        String::new()
+    } else {
+        format!("{fraction:0digits$x}")
+    };
+    match precision {
+        Some(precision) => hex.extend(std::iter::repeat_n('0', precision - digits)),
+        None => hex.truncate(hex.trim_end_matches('0').len()),
+    }
+    let point = if hex.is_empty() && !alternate {
+        ""
+    } else {
+        "."
+    };
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{lead}{point}{hex}p{sign}{}", exponent.unsigned_abs())
+}
+
+/// Splits off the longest prefix of `text` that `strtol` would read, after
+/// leading whitespace. Returns its value, saturated, and whether it
+/// overflowed.
+fn strtol(text: &str) -> (i64, &str, bool) {
+    let trimmed = text.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let unsigned = trimmed.trim_start_matches(['+', '-']);
+    let signs = trimmed.len() - unsigned.len();
+    let digits = unsigned.bytes().take_while(u8::is_ascii_digit).count();
+    if signs > 1 || digits == 0 {
+        return (0, text, false);
+    }
+    let number = &trimmed[..signs + digits];
+    let rest = &trimmed[signs + digits..];
+    number.parse().map_or_else(
+        |_| {
+            let value = if number.starts_with('-') {
+                i64::MIN
+            } else {
+                i64::MAX
+            };
+            (value, rest, true)
+        },
+        |value| (value, rest, false),
+    )
+}
+
+/// Like [`strtol`], but for `strtod`: the longest prefix that reads as a
+/// floating-point number, decimal or one of `inf`, `infinity` and `nan`.
+fn strtod(text: &str) -> (f64, &str) {
+    let trimmed = text.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let bytes = trimmed.as_bytes();
+    let digits_from = |at: usize| {
+        bytes[at.min(bytes.len())..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let mut len = usize::from(matches!(bytes.first(), Some(b'+' | b'-')));
+    let word = |name: &str| {
+        bytes.len() >= len + name.len()
+            && bytes[len..len + name.len()].eq_ignore_ascii_case(name.as_bytes())
+    };
+    if word("infinity") {
+        len += "infinity".len();
+    } else if word("inf") || word("nan") {
+        len += 3;
+    } else {
+        let whole = digits_from(len);
+        len += whole;
+        let mut fraction = 0;
+        if bytes.get(len) == Some(&b'.') {
+            fraction = digits_from(len + 1);
+            if whole + fraction > 0 {
+                len += 1 + fraction;
+            }
+        }
+        if whole + fraction == 0 {
+            return (0.0, text);
+        }
+        if matches!(bytes.get(len), Some(b'e' | b'E')) {
+            let sign = usize::from(matches!(bytes.get(len + 1), Some(b'+' | b'-')));
+            let exponent = digits_from(len + 1 + sign);
+            if exponent > 0 {
+                len += 1 + sign + exponent;
+            }
+        }
+    }
+    // Everything scanned is ASCII, and a form Rust reads too
+    trimmed[..len]
+        .parse()
+        .map_or((0.0, text), |value| (value, &trimmed[len..]))
+}
+
+impl Engine<'_> {
+    /// Reads the next argument as a C `int`, or a `long` if `long`, warning
+    /// like GNU m4 about anything that isn't one. Missing arguments are 0.
+    /// A value too big for an `int` is still returned whole, for the
+    /// conversion to wrap.
+    fn format_int<'t>(
+        &mut self,
+        args: &mut impl Iterator<Item = &'t str>,
+        long: bool,
+    ) -> Result<i64, Error> {
+        let Some(text) = args.next() else {
+            return Ok(0);
+        };
+        if text.is_empty() {
+            self.warn("empty string treated as 0")?;
+            return Ok(0);
+        }
+        let (value, rest, overflow) = strtol(text);
+        if !rest.is_empty() {
+            self.warn(&format!("non-numeric argument {text}"))?;
+        } else if text.starts_with(|c: char| c.is_ascii_whitespace()) {
+            self.warn("leading whitespace ignored")?;
+        } else if overflow || (!long && i32::try_from(value).is_err()) {
+            self.warn("numeric overflow detected")?;
+        }
+        Ok(value)
+    }
+
+    /// Reads the next argument as a C `double`.
+    fn format_double<'t>(
+        &mut self,
+        args: &mut impl Iterator<Item = &'t str>,
+    ) -> Result<f64, Error> {
+        let Some(text) = args.next() else {
+            return Ok(0.0);
+        };
+        if text.is_empty() {
+            self.warn("empty string treated as 0")?;
+            return Ok(0.0);
+        }
+        let (value, rest) = strtod(text);
+        if !rest.is_empty() {
+            self.warn(&format!("non-numeric argument {text}"))?;
+        } else if text.starts_with(|c: char| c.is_ascii_whitespace()) {
+            self.warn("leading whitespace ignored")?;
+        }
+        Ok(value)
+    }
+
+    /// Reads a field width or precision: digits, or `*` for an argument.
+    fn format_count<'t>(
+        &mut self,
+        format: &[char],
+        at: &mut usize,
+        args: &mut impl Iterator<Item = &'t str>,
+    ) -> Result<Option<i64>, Error> {
+        if format.get(*at) == Some(&'*') {
+            *at += 1;
+            return self.format_int(args, false).map(Some);
+        }
+        let digits = format[*at..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let count = format[*at..*at + digits].iter().fold(0i64, |count, &c| {
+            count
+                .saturating_mul(10)
+                .saturating_add(i64::from(c.to_digit(10).unwrap_or(0)))
+        });
+        *at += digits;
+        Ok((digits > 0).then_some(count))
+    }
+
+    /// Expands `format(format, args...)`.
+    #[allow(clippy::cast_possible_truncation, clippy::too_many_lines)]
+    pub(crate) fn format(&mut self, args: &[MacroValue]) -> Result<String, Error> {
+        let Some((format, rest)) = args.split_first() else {
+            return Ok(String::new());
+        };
+        let format: Vec<char> = format.text().chars().collect();
+        let mut args = rest.iter().map(MacroValue::text);
+        let mut result = String::new();
+        let mut at = 0;
+        while at < format.len() {
+            let c = format[at];
+            at += 1;
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+            if format.get(at) == Some(&'%') {
+                result.push('%');
+                at += 1;
+                continue;
+            }
+            let mut ok: Vec<char> = CONVERSIONS.chars().collect();
+            let mut spec = Spec {
+                left: false,
+                plus: false,
+                space: false,
+                zero: false,
+                alternate: false,
+                width: 0,
+                precision: None,
+                size: Size::Int,
+            };
+            // Flags rule out the conversions C leaves them undefined for
+            while let Some(&flag) = format.get(at) {
+                let ruled_out = match flag {
+                    '\'' => "aAceEosxX",
+                    '+' => {
+                        spec.plus = true;
+                        "cosuxX"
+                    }
+                    ' ' => {
+                        spec.space = true;
+                        "cosuxX"
+                    }
+                    '0' => {
+                        spec.zero = true;
+                        "cs"
+                    }
+                    '#' => {
+                        spec.alternate = true;
+                        "cdisu"
+                    }
+                    '-' => {
+                        spec.left = true;
+                        ""
+                    }
+                    _ => break,
+                };
+                ok.retain(|c| !ruled_out.contains(*c));
+                at += 1;
+            }
+            if let Some(width) = self.format_count(&format, &mut at, &mut args)? {
+                // A negative width from `*` means left justification
+                spec.left |= width < 0;
+                spec.width = usize::try_from(width.unsigned_abs()).unwrap_or(usize::MAX);
+            }
+            if format.get(at) == Some(&'.') {
+                at += 1;
+                ok.retain(|&c| c != 'c');
+                let precision = self.format_count(&format, &mut at, &mut args)?;
+                // A negative precision from `*` is as good as none
+                spec.precision = match precision {
+                    Some(precision) if precision < 0 => None,
+                    precision => Some(usize::try_from(precision.unwrap_or(0)).unwrap_or(0)),
+                };
+            }
+            match format.get(at) {
+                Some('l') => {
+                    at += 1;
+                    spec.size = Size::Long;
+                    ok.retain(|c| !"cs".contains(*c));
+                }
+                Some('h') => {
+                    at += 1;
+                    spec.size = Size::Short;
+                    if format.get(at) == Some(&'h') {
+                        at += 1;
+                        spec.size = Size::Char;
+                    }
+                    ok.retain(|c| !"aAceEfFgGs".contains(*c));
+                }
+                _ => {}
+            }
+            let Some(&conversion) = format.get(at).filter(|c| ok.contains(c)) else {
+                let whole: String = format.iter().collect();
+                self.warn(&format!("unrecognized specifier in `{whole}'"))?;
+                if at < format.len() {
+                    at += 1;
+                }
+                continue;
+            };
+            at += 1;
+            let text = match conversion {
+                'c' => {
+                    let code = truncate(self.format_int(&mut args, false)?) as u8;
+                    spec.pad("", &char::from(code).to_string(), false)
+                }
+                's' => {
+                    let text = args.next().unwrap_or("");
+                    let text: String = spec.precision.map_or_else(
+                        || text.into(),
+                        |precision| text.chars().take(precision).collect(),
+                    );
+                    spec.pad("", &text, false)
+                }
+                'd' | 'i' => spec.signed(self.format_int(&mut args, spec.size == Size::Long)?),
+                'o' | 'u' | 'x' | 'X' => {
+                    let value = self.format_int(&mut args, spec.size == Size::Long)?;
+                    spec.unsigned(value, conversion)
+                }
+                _ => spec.float(self.format_double(&mut args)?, conversion),
+            };
+            result.push_str(&text);
+        }
+        Ok(result)
+    }
+}
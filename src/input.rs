@@ -0,0 +1,242 @@
+use crate::builtins::Builtin;
+
+/// Pending input. Expansions are pushed on top of whatever is being read, so
+/// the scanner sees their text exactly as if it had appeared in the input at
+/// that point: a token or comment can start in one source and end in the one
+/// below it.
+pub struct Input {
+    sources: Vec<Source>,
+    /// Where the last input file ended, reported once none is left.
+    last_location: Location,
+    /// Whether that file ended after the last byte read, so nothing from
+    /// whatever included it has been read yet.
+    file_ended: bool,
+}
+
+/// A line of an input file, for `__file__` and `__line__`.
+#[derive(Clone)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+}
+
+enum Source {
+    Text {
+        data: Vec<u8>,
+        pos: usize,
+        /// Set for a whole input file, whose lines are counted.
+        location: Option<Location>,
+    },
+    /// A builtin token, as produced by `defn`, carried out of band so it can
+    /// never be confused with text.
+    Builtin(&'static Builtin),
+}
+
+/// One unit of input: a byte of text or a whole builtin token.
+pub enum Item {
+    Byte(u8),
+    Builtin(&'static Builtin),
+}
+
+impl Input {
+    pub const fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+            last_location: Location {
+                file: String::new(),
+                line: 0,
+            },
+            file_ended: false,
+        }
+    }
+
+    /// Queues `data` to be read before anything already pending.
+    pub fn push(&mut self, data: Vec<u8>) {
+        if !data.is_empty() {
+            self.sources.push(Source::Text {
+                data,
+                pos: 0,
+                location: None,
+            });
+        }
+    }
+
+    /// Queues the contents of the input file `name`, to be read before
+    /// anything already pending.
+    pub fn push_file(&mut self, name: String, data: Vec<u8>) {
+        self.sources.push(Source::Text {
+            data,
+            pos: 0,
+            location: Some(Location {
+                file: name,
+                line: 1,
+            }),
+        });
+    }
+
+    /// The input file being read and the line the scanner is on.
+    pub fn location(&self) -> &Location {
+        self.sources
+            .iter()
+            .rev()
+            .find_map(|source| match source {
+                Source::Text { location, .. } => location.as_ref(),
+                Source::Builtin(_) => None,
+            })
+            .unwrap_or(&self.last_location)
+    }
+
+    /// Where an input file ended, if it ended after the last byte read.
+    pub const fn ended_file(&self) -> Option<&Location> {
+        if self.file_ended {
+            Some(&self.last_location)
+        } else {
+            None
+        }
+    }
+
+    /// The names of the input files being read, innermost first.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().rev().filter_map(|source| match source {
+            Source::Text {
+                location: Some(location),
+                ..
+            } => Some(location.file.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Queues a builtin token to be read before anything already pending.
+    pub fn push_builtin(&mut self, builtin: &'static Builtin) {
+        self.sources.push(Source::Builtin(builtin));
+    }
+
+    /// The next byte of text, or `None` at the end of input or when a builtin
+    /// token comes first.
+    pub fn peek(&mut self) -> Option<u8> {
+        loop {
+            match self.sources.last()? {
+                Source::Text { data, pos, .. } => {
+                    if let Some(&c) = data.get(*pos) {
+                        return Some(c);
+                    }
+                    if let Some(Source::Text {
+                        location: Some(location),
+                        ..
+                    }) = self.sources.pop()
+                    {
+                        self.last_location = location;
+                        self.file_ended = true;
+                    }
+                }
+                Source::Builtin(_) => return None,
+            }
+        }
+    }
+
+    /// How many sources are pending; see [`pushed_since`](Self::pushed_since).
+    pub const fn depth(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Everything queued on top of the first `depth` sources, in the order
+    /// it will be read, with builtin tokens shown as `<name>`. Input files,
+    /// as `include` queues, aren't expansions and are left out.
+    pub fn pushed_since(&self, depth: usize) -> String {
+        let mut text = String::new();
+        for source in self.sources.iter().skip(depth).rev() {
+            match source {
+                Source::Text {
+                    location: Some(_), ..
+                } => {}
+                Source::Text { data, pos, .. } => {
+                    text.extend(data[*pos..].iter().map(|&c| char::from(c)));
+                }
+                Source::Builtin(builtin) => {
+                    text.push('<');
+                    text.push_str(builtin.name());
+                    text.push('>');
+                }
+            }
+        }
+        text
+    }
+
+    /// Whether the pending text starts with `prefix`, which may span several
+    /// sources. A builtin token ends the text that can match.
+    pub fn looking_at(&self, prefix: &[u8]) -> bool {
+        let mut rest = prefix;
+        for source in self.sources.iter().rev() {
+            if rest.is_empty() {
+                break;
+            }
+            match source {
+                Source::Text { data, pos, .. } => {
+                    let data = &data[*pos..];
+                    let len = data.len().min(rest.len());
+                    if data[..len] != rest[..len] {
+                        return false;
+                    }
+                    rest = &rest[len..];
+                }
+                Source::Builtin(_) => return false,
+            }
+        }
+        rest.is_empty()
+    }
+
+    /// Discards the next `len` bytes of text.
+    pub fn skip(&mut self, len: usize) {
+        for _ in 0..len {
+            self.next();
+        }
+    }
+
+    pub fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.file_ended = false;
+        if let Some(Source::Text { pos, location, .. }) = self.sources.last_mut() {
+            *pos += 1;
+            if let (Some(location), b'\n') = (location, c) {
+                location.line += 1;
+            }
+        }
+        Some(c)
+    }
+
+    pub fn next_item(&mut self) -> Option<Item> {
+        if let Some(c) = self.next() {
+            return Some(Item::Byte(c));
+        }
+        match self.sources.pop()? {
+            Source::Builtin(builtin) => {
+                self.file_ended = false;
+                Some(Item::Builtin(builtin))
+            }
+            Source::Text { .. } => None,
+        }
+    }
+
+    /// Like [`next_item`](Self::next_item), but `None` at the end of the
+    /// innermost input file instead of going on into whatever included it.
+    pub fn next_item_in_file(&mut self) -> Option<Item> {
+        if self.file_ended {
+            return None;
+        }
+        loop {
+            match self.sources.last()? {
+                Source::Text {
+                    data,
+                    pos,
+                    location,
+                } if *pos == data.len() => {
+                    if location.is_some() {
+                        return None;
+                    }
+                    self.sources.pop();
+                }
+                _ => return self.next_item(),
+            }
+        }
+    }
+}
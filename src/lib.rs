@@ -0,0 +1,29 @@
+#![deny(warnings)]
+#![warn(clippy::all, clippy::nursery, clippy::pedantic)]
+//! M4 implementation by LC.
+//!
+//! The [`Engine`] holds all macro-processing state; construct one with an
+//! [`EngineBuilder`]. The `lc-m4` binary is a thin command-line front end
+//! over the same builder.
+
+mod builder;
+mod builtins;
+mod dump;
+mod engine;
+mod error;
+mod eval;
+mod format;
+mod input;
+mod regex;
+mod symtab;
+mod system;
+mod trace;
+
+pub use builder::{BuildError, EngineBuilder};
+pub use dump::{write_json_text, DumpFormat};
+pub use engine::{Delimiters, Engine};
+pub use error::{Error, Stream};
+
+/// The version of lc-m4, which `__lc_m4_version__` expands to; `__lc_m4__`
+/// gives it as `--version` prints it.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
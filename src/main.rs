@@ -2,401 +2,382 @@
 #![warn(clippy::all, clippy::nursery, clippy::pedantic)]
 
 use std::env;
+use std::ffi::OsString;
 use std::fs::File;
-use std::hint::unreachable_unchecked;
 use std::io;
 use std::io::{Read, Write};
-use std::mem::drop;
+use std::path::{Path, PathBuf};
 use std::process;
 use std::vec::Vec;
 
+use lc_m4::{DumpFormat, EngineBuilder, Error, Stream, VERSION};
+
 fn help() {
     println!("We support reload-state. That's what you care about autom4te, right?");
 }
 
 enum Flag {
-    DebugFile(String),
+    DebugFile(PathBuf),
+    Define(Vec<u8>, Vec<u8>),
     DebugFlags(String),
+    DumpDefinitions(PathBuf, DumpFormat),
+    Extensions,
     FatalWarning(bool),
-    File(Box<dyn Read>),
+    File(Vec<u8>),
     GnulyCorrect(bool),
-    IncludePath(String),
+    IncludePath(PathBuf),
     NestingLimit(u64),
+    PrefixBuiltins,
+    Quiet,
     ReloadState(Box<dyn Read>),
+    Synclines,
     Trace(String),
-    Undef(String),
+    Undef(Vec<u8>),
+    WarnMacroSequence(String),
+    WordRegexp(String),
 }
 
-enum MacroValue {
-    Text(String),
-    BuiltinFunction(String),
+/// What an option does with the value that may follow it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Takes {
+    Nothing,
+    /// A value, given after `=` or in the next argument, or attached to a
+    /// short option or as the next argument.
+    Value,
+    /// A value only if given after `=` or attached to a short option.
+    OptionalValue,
 }
 
-fn parse_args<I: Iterator<Item = String>>(mut args: I) -> (String, Vec<Flag>) {
-    let prg_name = args.next().unwrap_or_else(|| "m4".into()); // If we were (erroneously) not handed a program name, gracefully handle it
-    let mut flags: Vec<Flag> = Vec::new();
-    let mut any_files = false;
-    for arg in args {
-        eprintln!("{}", arg);
-        if arg == "--help" {
-            help();
-            process::exit(0);
-        } else if arg == "--fatal-warning" {
-            flags.push(Flag::FatalWarning(true));
-        } else if arg == "--gnu" {
-            flags.push(Flag::GnulyCorrect(true));
-        } else if arg == "--traditional" {
-            flags.push(Flag::GnulyCorrect(false));
-        } else if let Some(debug_flags) = arg.strip_prefix("--debug=") {
-            flags.push(Flag::DebugFlags(debug_flags.into()));
-        } else if let Some(debug_file) = arg.strip_prefix("--debugfile=") {
-            flags.push(Flag::DebugFile(debug_file.into()));
-        } else if let Some(include_path) = arg.strip_prefix("--include=") {
-            flags.push(Flag::IncludePath(include_path.into()));
-        } else if let Some(nesting_limit) = arg.strip_prefix("--nesting-limit=") {
-            flags.push(Flag::NestingLimit(nesting_limit.parse().unwrap_or_else(
-                |_| {
-                    eprintln!("lc-m4: Nesting limit must be a number");
-                    process::exit(1);
-                },
-            )));
-        } else if let Some(reload_state) = arg.strip_prefix("--reload-state=") {
-            flags.push(Flag::ReloadState(Box::new(
-                File::open(&reload_state).unwrap_or_else(|_| {
-                    eprintln!("Couldn't open file {} for reading!", &arg);
-                    process::exit(1);
-                }),
-            )));
-        } else if let Some(traced) = arg.strip_prefix("--trace=") {
-            flags.push(Flag::Trace(traced.into()));
-        } else if let Some(undef) = arg.strip_prefix("--undefine=") {
-            flags.push(Flag::Undef(undef.into()));
-        } else if arg == "-" {
-            any_files = true;
-            flags.push(Flag::File(Box::new(io::stdin())));
-        } else if arg.starts_with('-') {
-            eprintln!("Unrecognized arg: {}", arg);
-            process::exit(1);
-        } else {
-            any_files = true;
-            flags.push(Flag::File(Box::new(File::open(&arg).unwrap_or_else(
-                |_| {
-                    eprintln!("Couldn't open file {} for reading!", &arg);
-                    process::exit(1);
-                },
-            ))));
-        }
-    }
-    if !any_files {
-        flags.push(Flag::File(Box::new(io::stdin())));
-    }
-    (prg_name, flags)
-}
+/// Every option, by long name and short letter if it has one. The letters
+/// are GNU m4's.
+const OPTIONS: &[(&str, Option<char>, Takes)] = &[
+    ("debug", Some('d'), Takes::OptionalValue),
+    ("debugfile", Some('o'), Takes::Value),
+    ("define", Some('D'), Takes::Value),
+    ("dump-definitions", None, Takes::Value),
+    ("extensions", None, Takes::Nothing),
+    ("fatal-warning", Some('E'), Takes::Nothing),
+    ("gnu", Some('g'), Takes::Nothing),
+    ("help", None, Takes::Nothing),
+    ("include", Some('I'), Takes::Value),
+    ("nesting-limit", Some('L'), Takes::Value),
+    ("prefix-builtins", Some('P'), Takes::Nothing),
+    ("quiet", Some('Q'), Takes::Nothing),
+    ("reload-state", Some('R'), Takes::Value),
+    ("synclines", Some('s'), Takes::Nothing),
+    ("trace", Some('t'), Takes::Value),
+    ("traditional", Some('G'), Takes::Nothing),
+    ("undefine", Some('U'), Takes::Value),
+    ("version", None, Takes::Nothing),
+    ("warn-macro-sequence", None, Takes::OptionalValue),
+    ("word-regexp", Some('W'), Takes::Value),
+];
 
-fn read_int<I: Iterator<Item = u8>>(data: &mut I, sep: u8) -> i64 {
-    let mut result: i64 = 0;
-    let mut negative = false;
-    for c in data {
-        if c == sep {
-            break;
-        }
-        if c == b'-' {
-            negative = true;
-        } else {
-            result *= 10;
-            result += i64::from(c - b'0');
-        }
-    }
-    result * if negative { -1 } else { 1 }
+fn usage_error(prg_name: &str, message: &str) -> ! {
+    eprintln!("{prg_name}: {message}");
+    process::exit(1);
 }
 
-fn print_to_diversion(cur_diversion: i64, content: &str, diversion_data: &mut Vec<String>) {
-    if cur_diversion == 0 {
-        print!("{}", content);
-    } else if cur_diversion > 0 {
-        let target = usize::try_from(cur_diversion - 1)
-            .unwrap_or_else(|_| unsafe { unreachable_unchecked() });
-        while diversion_data.len() <= target {
-            diversion_data.push(String::new());
-        }
-        diversion_data[target].push_str(content);
+/// The bytes of a command-line argument, which macro names and bodies are
+/// made of. Off unix, where arguments aren't bytes, anything that isn't
+/// UTF-8 is replaced.
+fn arg_bytes(arg: OsString) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        arg.into_vec()
+    }
+    #[cfg(not(unix))]
+    {
+        arg.to_string_lossy().into_owned().into_bytes()
     }
 }
 
-pub struct Delimiters {
-    comment_start: u8,
-    comment_end: u8,
-    quote_start: u8,
-    quote_end: u8,
-}
-
-impl Delimiters {
-    #[must_use]
-    pub const fn new() -> Self {
-        Self {
-            comment_start: b'#',
-            comment_end: b'\n',
-            quote_start: b'`',
-            quote_end: b'\'',
-        }
+/// The file name an argument's bytes stand for.
+fn path_of(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(bytes.to_vec()).into()
+    }
+    #[cfg(not(unix))]
+    {
+        lossy(bytes).into()
     }
 }
 
-fn exec_file<F: Read>(
-    file: &mut F,
-    def_stack: &mut Vec<(String, MacroValue)>,
-    cur_diversion: &mut i64,
-    diversion_data: &mut Vec<String>,
-    delimiters: &mut Delimiters,
-) {
-    let mut data: Vec<u8> = Vec::new();
-    file.read_to_end(&mut data).unwrap_or_else(|e| {
-        eprintln!("Couldn't read an input file: {}", e);
-        process::exit(1);
-    });
-    let mut data = data.iter().copied();
-    process_text(
-        &mut data,
-        def_stack,
-        cur_diversion,
-        diversion_data,
-        delimiters,
-    );
+/// An argument's bytes as text, for the options that only make sense as
+/// UTF-8 and for messages.
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
 }
 
-#[allow(dead_code)]
-fn process_macro(
-    cur_tok: &str,
-    def_stack: &mut Vec<(String, MacroValue)>,
-    cur_diversion: &mut i64,
-    diversion_data: &mut Vec<String>,
-    _delimiters: &mut Delimiters,
-) {
-    let mut matched = false;
-    for def in def_stack.into_iter().rev() {
-        if &def.0 == cur_tok {
-            matched = true;
-            eprintln!("Matched {}", def.0); // TODO
-            break;
+/// The flag for the option `name`, given `value`.
+fn option_flag(prg_name: &str, name: &str, value: Option<Vec<u8>>) -> Flag {
+    let value = value.unwrap_or_default();
+    match name {
+        "help" => {
+            help();
+            process::exit(0);
         }
-    }
-    if !matched {
-        print_to_diversion(*cur_diversion, &cur_tok, diversion_data)
-    }
-}
-
-fn skip_comment<I: Iterator<Item = u8>>(data: &mut I, end: u8) {
-    for c in data {
-        if c == end {
-            break;
+        "version" => {
+            println!("lc-m4 {VERSION}");
+            process::exit(0);
         }
+        // Like GNU m4, `-d` alone means the default flags
+        "debug" if value.is_empty() => Flag::DebugFlags("aeq".into()),
+        "debug" => Flag::DebugFlags(lossy(&value)),
+        "debugfile" => Flag::DebugFile(path_of(&value)),
+        "define" => match value.iter().position(|&b| b == b'=') {
+            Some(at) => Flag::Define(value[..at].to_vec(), value[at + 1..].to_vec()),
+            None => Flag::Define(value, Vec::new()),
+        },
+        // A trailing `:word` names the format; any other colon is part of
+        // the file name
+        "dump-definitions" => match value.iter().rposition(|&b| b == b':') {
+            Some(at)
+                if value.len() > at + 1
+                    && value[at + 1..].iter().all(u8::is_ascii_alphanumeric) =>
+            {
+                let format = lossy(&value[at + 1..]);
+                let format = DumpFormat::from_name(&format).unwrap_or_else(|| {
+                    usage_error(prg_name, &format!("unknown dump format `{format}'"))
+                });
+                Flag::DumpDefinitions(path_of(&value[..at]), format)
+            }
+            _ => Flag::DumpDefinitions(path_of(&value), DumpFormat::Text),
+        },
+        "extensions" => Flag::Extensions,
+        "fatal-warning" => Flag::FatalWarning(true),
+        "gnu" => Flag::GnulyCorrect(true),
+        "include" => Flag::IncludePath(path_of(&value)),
+        "nesting-limit" => Flag::NestingLimit(
+            lossy(&value)
+                .parse()
+                .unwrap_or_else(|_| usage_error(prg_name, "Nesting limit must be a number")),
+        ),
+        "prefix-builtins" => Flag::PrefixBuiltins,
+        "quiet" => Flag::Quiet,
+        "reload-state" => {
+            Flag::ReloadState(Box::new(File::open(path_of(&value)).unwrap_or_else(|_| {
+                usage_error(
+                    prg_name,
+                    &format!("Couldn't open file {} for reading!", lossy(&value)),
+                )
+            })))
+        }
+        "synclines" => Flag::Synclines,
+        "trace" => Flag::Trace(lossy(&value)),
+        "traditional" => Flag::GnulyCorrect(false),
+        "undefine" => Flag::Undef(value),
+        "warn-macro-sequence" if value.is_empty() => {
+            Flag::WarnMacroSequence(EngineBuilder::DEFAULT_MACRO_SEQUENCE.into())
+        }
+        "warn-macro-sequence" => Flag::WarnMacroSequence(lossy(&value)),
+        "word-regexp" => Flag::WordRegexp(lossy(&value)),
+        _ => unreachable!("option `{name}' is in OPTIONS but has no flag"),
     }
 }
 
-fn process_text<I: Iterator<Item = u8>>(
-    data: &mut I,
-    def_stack: &mut Vec<(String, MacroValue)>,
-    cur_diversion: &mut i64,
-    diversion_data: &mut Vec<String>,
-    delimiters: &mut Delimiters,
-) {
-    let mut cur_tok = String::new();
-    while let Some(c) = data.next() {
-        match c {
-            x if x == delimiters.comment_start => {
-                process_macro(
-                    &cur_tok,
-                    def_stack,
-                    cur_diversion,
-                    diversion_data,
-                    delimiters,
-                );
-                skip_comment(data, delimiters.comment_end);
-            }
-            b' ' | b'\t' | b'\r' | b'\n' => {
-                process_macro(
-                    &cur_tok,
-                    def_stack,
-                    cur_diversion,
-                    diversion_data,
-                    delimiters,
-                );
-                print_to_diversion(*cur_diversion, &(c as char).to_string()[..], diversion_data);
-                cur_tok = String::new();
+/// Parses the command line like `getopt_long`: long options may be given
+/// as `--name=value` or `--name value`, short ones may be bundled, as in
+/// `-sE`, with a value attached, as in `-Idir`, or in the next argument.
+/// Everything after `--` is a file. Arguments are taken as bytes, so they
+/// needn't be UTF-8.
+fn parse_args<I: Iterator<Item = Vec<u8>>>(mut args: I) -> (String, Vec<Flag>) {
+    let prg_name = args.next().map_or_else(|| "m4".into(), |name| lossy(&name)); // If we were (erroneously) not handed a program name, gracefully handle it
+    let mut flags: Vec<Flag> = Vec::new();
+    let mut files = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == b"--" {
+            files.extend(args.by_ref());
+        } else if let Some(long) = arg.strip_prefix(b"--") {
+            let (name, value) = long
+                .iter()
+                .position(|&b| b == b'=')
+                .map_or((long, None), |at| {
+                    (&long[..at], Some(long[at + 1..].to_vec()))
+                });
+            let Some(&(name, _, takes)) = OPTIONS.iter().find(|option| option.0.as_bytes() == name)
+            else {
+                usage_error(&prg_name, &format!("Unrecognized arg: {}", lossy(&arg)));
+            };
+            let value = match (takes, value) {
+                (Takes::Nothing, Some(_)) => usage_error(
+                    &prg_name,
+                    &format!("option `--{name}' doesn't allow an argument"),
+                ),
+                (Takes::Value, None) => Some(args.next().unwrap_or_else(|| {
+                    usage_error(
+                        &prg_name,
+                        &format!("option `--{name}' requires an argument"),
+                    )
+                })),
+                (_, value) => value,
+            };
+            flags.push(option_flag(&prg_name, name, value));
+        } else if let Some(shorts) = arg.strip_prefix(b"-").filter(|shorts| !shorts.is_empty()) {
+            for (i, &letter) in shorts.iter().enumerate() {
+                let letter = char::from(letter);
+                let Some(&(name, _, takes)) = OPTIONS
+                    .iter()
+                    .find(|option| letter.is_ascii() && option.1 == Some(letter))
+                else {
+                    let letter = lossy(&shorts[i..]).chars().next().unwrap_or_default();
+                    usage_error(&prg_name, &format!("Unrecognized arg: -{letter}"));
+                };
+                if takes == Takes::Nothing {
+                    flags.push(option_flag(&prg_name, name, None));
+                    continue;
+                }
+                // The rest of the argument is the value, if there is a rest
+                let rest = &shorts[i + 1..];
+                let value = if !rest.is_empty() {
+                    Some(rest.to_vec())
+                } else if takes == Takes::Value {
+                    Some(args.next().unwrap_or_else(|| {
+                        usage_error(
+                            &prg_name,
+                            &format!("option `-{letter}' requires an argument"),
+                        )
+                    }))
+                } else {
+                    None
+                };
+                flags.push(option_flag(&prg_name, name, value));
+                break;
             }
-            _ => cur_tok.push(c as char),
+        } else {
+            files.push(arg);
         }
     }
-    process_macro(
-        &cur_tok,
-        def_stack,
-        cur_diversion,
-        diversion_data,
-        delimiters,
-    );
+    if files.is_empty() {
+        files.push(b"-".to_vec());
+    }
+    flags.extend(files.into_iter().map(Flag::File));
+    (prg_name, flags)
 }
 
-fn exec_reload_state<F: Read>(
-    file: &mut F,
-    def_stack: &mut Vec<(String, MacroValue)>,
-    cur_diversion: &mut i64,
-    diversion_data: &mut Vec<String>,
-    delimiters: &mut Delimiters,
-) {
-    let mut data: Vec<u8> = Vec::new();
-    file.read_to_end(&mut data).unwrap_or_else(|e| {
-        eprintln!("Couldn't read a reload state file: {}", e);
-        process::exit(1);
-    });
-    let mut data = data.iter().copied();
-    while let Some(c) = data.next() {
-        if c == delimiters.comment_start {
-            skip_comment(&mut data, delimiters.comment_end);
-        } else if c == b'C' {
-            let start_len = read_int(&mut data, b',');
-            let end_len = read_int(&mut data, b'\n');
-            if start_len != 1 || end_len != 1 {
-                eprintln!("Comment with multiple-character delimiters? Unheard of!");
-                process::exit(1);
-            }
-            delimiters.comment_start = data.next().unwrap_or(b'#');
-            delimiters.comment_end = data.next().unwrap_or(b'\n');
-            let c = data.next();
-            if matches!(c, None) || !matches!(c, Some(b'\n')) {
-                eprintln!("Syntax error in reload state file: missing newline after C declaration");
-                process::exit(1);
-            }
-        } else if c == b'D' {
-            let div_num = read_int(&mut data, b',');
-            let content_len = read_int(&mut data, b'\n');
-            let mut content = String::new();
-            for _ in 0..content_len {
-                content.push(data.next().unwrap_or(b'#') as char);
-            }
-            *cur_diversion = div_num;
-            print_to_diversion(*cur_diversion, &content, diversion_data);
-            let c = data.next();
-            if matches!(c, None) || !matches!(c, Some(b'\n')) {
-                eprintln!("Syntax error in reload state file: missing newline after D declaration");
-                process::exit(1);
-            }
-        } else if c == b'F' {
-            let name_len = read_int(&mut data, b',');
-            let value_len = read_int(&mut data, b'\n');
-            let mut name = String::new();
-            for _ in 0..name_len {
-                name.push(data.next().unwrap_or(b'#') as char);
-            }
-            let mut value = String::new();
-            for _ in 0..value_len {
-                value.push(data.next().unwrap_or(b'#') as char);
-            }
-            def_stack.push((name, MacroValue::BuiltinFunction(value)));
-            let c = data.next();
-            if matches!(c, None) || !matches!(c, Some(b'\n')) {
-                eprintln!("Syntax error in reload state file: missing newline after T declaration");
-                process::exit(1);
-            }
-        } else if c == b'Q' {
-            let start_len = read_int(&mut data, b',');
-            let end_len = read_int(&mut data, b'\n');
-            if start_len != 1 || end_len != 1 {
-                eprintln!("Quote with multiple-character delimiters? Unheard of!");
-                process::exit(1);
-            }
-            delimiters.quote_start = data.next().unwrap_or(b'#');
-            delimiters.quote_end = data.next().unwrap_or(b'\n');
-            let c = data.next();
-            if matches!(c, None) || !matches!(c, Some(b'\n')) {
-                eprintln!("Syntax error in reload state file: missing newline after Q declaration");
-                process::exit(1);
-            }
-        } else if c == b'T' {
-            let name_len = read_int(&mut data, b',');
-            let value_len = read_int(&mut data, b'\n');
-            let mut name = String::new();
-            for _ in 0..name_len {
-                name.push(data.next().unwrap_or(b'#') as char);
-            }
-            let mut value = String::new();
-            for _ in 0..value_len {
-                value.push(data.next().unwrap_or(b'#') as char);
-            }
-            def_stack.push((name, MacroValue::Text(value)));
-            let c = data.next();
-            if matches!(c, None) || !matches!(c, Some(b'\n')) {
-                eprintln!("Syntax error in reload state file: missing newline after T declaration");
-                process::exit(1);
-            }
-        } else if c == b'V' {
-            let c = data.next();
-            if matches!(c, None) || !matches!(c, Some(b'1')) {
-                eprintln!(
-                    "Syntax error in reload state file: incorrect/missing version in V declaration"
-                );
-                process::exit(1);
-            }
-            let c = data.next();
-            if matches!(c, None) || !matches!(c, Some(b'\n')) {
-                eprintln!("Syntax error in reload state file: missing newline after V declaration");
-                process::exit(1);
+/// Exit status for output that couldn't be written (`EX_IOERR` in sysexits.h)
+const EXIT_WRITE_FAILURE: i32 = 74;
+
+/// Reports an error that stopped the engine and picks the exit status.
+fn fail(prg_name: &str, error: &Error, debug_file: Option<&Path>, fatal_warnings: bool) -> i32 {
+    match error {
+        Error::Write { stream, source } => {
+            // Like other filters, go quietly when whoever reads stdout has gone
+            if *stream == Stream::Output
+                && source.kind() == io::ErrorKind::BrokenPipe
+                && !fatal_warnings
+            {
+                return 0;
             }
-        } else {
-            print!("{}", c as char);
+            let path = match stream {
+                Stream::Output => "stdout".into(),
+                Stream::Debug => {
+                    debug_file.map_or_else(|| "stderr".into(), |file| file.display().to_string())
+                }
+                Stream::Diagnostics => "stderr".into(),
+            };
+            eprintln!("{prg_name}: write error on {path}: {source}");
+            EXIT_WRITE_FAILURE
+        }
+        Error::Read(_) | Error::NestingLimit(_) | Error::FrozenSyntax(_) => {
+            eprintln!("{prg_name}: {error}");
+            1
         }
     }
 }
 
 fn main() {
-    let (prg_name, flags) = parse_args(env::args());
-    let mut debug_flags = "aeq".into();
-    let mut debug_out: Box<dyn Write> = Box::new(io::stderr());
-    let mut nesting_limit: u64 = 1024;
-    let mut traced = Vec::new();
-    let mut def_stack = vec![(String::from("divert"), MacroValue::BuiltinFunction(String::from("divert")))];
-    let mut cur_diversion = 0;
-    let mut diversion_data = Vec::new();
-    let mut delimiters = Delimiters::new();
+    let (prg_name, flags) = parse_args(env::args_os().map(arg_bytes));
+    let mut builder = EngineBuilder::new().program_name(&prg_name);
+    let mut actions = Vec::new();
+    let mut dump = None;
+    let mut debug_file = None;
+    let mut fatal_warnings = false;
     for f in flags {
         match f {
             Flag::DebugFile(x) => {
-                debug_out = match File::create(x) {
-                    io::Result::Ok(x) => Box::new(x),
+                builder = builder.debug_output(match File::create(&x) {
+                    io::Result::Ok(x) => x,
                     io::Result::Err(x) => {
-                        eprintln!("{}: Error creating debug file: {}", prg_name, x);
+                        eprintln!("{prg_name}: Error creating debug file: {x}");
                         process::exit(1)
                     }
-                }
+                });
+                debug_file = Some(x);
+            }
+            Flag::DebugFlags(x) => builder = builder.debug_flags(x),
+            Flag::DumpDefinitions(file, format) => dump = Some((file, format)),
+            Flag::Extensions => builder = builder.extensions(true),
+            Flag::FatalWarning(x) => {
+                fatal_warnings = x;
+                builder = builder.fatal_warnings(x);
             }
-            Flag::DebugFlags(x) => debug_flags = x,
-            Flag::FatalWarning(_)
-            | Flag::GnulyCorrect(_)
-            | Flag::IncludePath(_)
-            | Flag::Undef(_) => {} // We don't care yet
-            Flag::File(mut x) => {
-                exec_file(
-                    &mut x,
-                    &mut def_stack,
-                    &mut cur_diversion,
-                    &mut diversion_data,
-                    &mut delimiters,
-                );
+            Flag::GnulyCorrect(x) => builder = builder.gnu(x),
+            Flag::IncludePath(x) => builder = builder.include_path(x),
+            Flag::NestingLimit(x) => builder = builder.nesting_limit(x),
+            Flag::PrefixBuiltins => builder = builder.prefix_builtins(true),
+            Flag::Quiet => builder = builder.quiet(true),
+            Flag::Synclines => builder = builder.synclines(true),
+            Flag::Trace(x) => builder = builder.trace(x),
+            Flag::WarnMacroSequence(x) => builder = builder.warn_macro_sequence(x),
+            Flag::WordRegexp(x) => builder = builder.word_regexp(x),
+            Flag::File(_) => actions.push(f),
+            // The frozen state and then the command-line definitions and
+            // undefinitions, in the order given, apply before any input is
+            // read
+            Flag::ReloadState(_) => actions.insert(0, f),
+            Flag::Define(..) | Flag::Undef(_) => {
+                let first_file = actions
+                    .iter()
+                    .position(|f| matches!(f, Flag::File(_)))
+                    .unwrap_or(actions.len());
+                actions.insert(first_file, f);
             }
-            Flag::NestingLimit(x) => nesting_limit = x,
-            Flag::ReloadState(mut x) => {
-                exec_reload_state(
-                    &mut x,
-                    &mut def_stack,
-                    &mut cur_diversion,
-                    &mut diversion_data,
-                    &mut delimiters,
-                );
+        }
+    }
+    let mut engine = builder.build().unwrap_or_else(|e| {
+        eprintln!("{prg_name}: {e}");
+        process::exit(1);
+    });
+    // Like GNU m4, a missing input file is reported but doesn't stop the run
+    let mut status = 0;
+    let result = actions
+        .into_iter()
+        .try_for_each(|f| match f {
+            Flag::File(x) if x == b"-" => engine.process(&mut io::stdin()),
+            Flag::File(x) => match engine.open_input(&path_of(&x)) {
+                Ok(mut file) => engine.process_named(&x, &mut file),
+                Err(e) => {
+                    // After what the earlier files wrote; if that can't be
+                    // written, the engine's next write reports it
+                    let _ = io::stdout().flush();
+                    eprintln!("{prg_name}: cannot open `{}': {e}", lossy(&x));
+                    status = 1;
+                    Ok(())
+                }
+            },
+            Flag::ReloadState(mut x) => engine.reload_state(&mut x),
+            Flag::Define(name, value) => engine.define(name, value),
+            Flag::Undef(x) => {
+                engine.undefine(x);
+                Ok(())
             }
-            Flag::Trace(x) => traced.push(x),
+            _ => Ok(()),
+        })
+        .and_then(|()| engine.finish());
+    if let Err(e) = result {
+        process::exit(fail(&prg_name, &e, debug_file.as_deref(), fatal_warnings));
+    }
+    if let Some((file, format)) = dump {
+        let written =
+            File::create(&file).and_then(|mut out| engine.dump_definitions(&mut out, format));
+        if let Err(e) = written {
+            eprintln!("{prg_name}: write error on {}: {e}", file.display());
+            process::exit(EXIT_WRITE_FAILURE);
         }
     }
-    drop(debug_flags);
-    drop(debug_out);
-    let _ = nesting_limit;
-    drop(traced);
+    process::exit(status.max(engine.exit_status()));
 }
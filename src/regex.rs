@@ -0,0 +1,578 @@
+//! Emacs-style regular expressions, the syntax GNU m4 uses for `changeword`,
+//! `regexp` and `patsubst`.
+//!
+//! Groups are `\(`...`\)`, alternation is `\|`, and `*`, `+` and `?` are
+//! postfix operators. `^` and `$` are anchors only at the start and end of a
+//! branch and match at line boundaries; `` \` `` and `\'` match at the ends of
+//! the whole text. `\w`, `\W`, `\b`, `\B`, `\<` and `\>` work on word
+//! characters (letters, digits and `_`), and `\1` to `\9` are back-references.
+//! Like GNU regex, the leftmost match is the longest one starting there.
+
+/// Where a zero-width assertion can hold.
+#[derive(Clone, Copy)]
+enum Assertion {
+    LineStart,
+    LineEnd,
+    TextStart,
+    TextEnd,
+    WordBoundary,
+    NotWordBoundary,
+    WordStart,
+    WordEnd,
+}
+
+enum Node {
+    Byte(u8),
+    Any,
+    Set(Box<[bool; 256]>),
+    Assert(Assertion),
+    Group(usize, Box<Self>),
+    Backref(usize),
+    Concat(Vec<Self>),
+    Alt(Vec<Self>),
+    Star(Box<Self>),
+    Plus(Box<Self>),
+    Optional(Box<Self>),
+}
+
+enum Inst {
+    Byte(u8),
+    Any,
+    Set(Box<[bool; 256]>),
+    Assert(Assertion),
+    Backref(usize),
+    Save(usize),
+    /// Try the first target, then the second.
+    Split(usize, usize),
+    Jump(usize),
+    /// Records where an iteration of a loop starts.
+    Mark(usize),
+    /// Fails if a loop iteration matched nothing, which would loop forever.
+    Progress(usize),
+    Match,
+}
+
+/// The span of each group in a match; group 0 is the whole match.
+pub type Captures = Vec<Option<(usize, usize)>>;
+
+/// A compiled regular expression.
+pub struct Regex {
+    program: Vec<Inst>,
+    groups: usize,
+    loops: usize,
+    backrefs: bool,
+}
+
+const fn is_word(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// The bytes in a `[:name:]` class.
+fn class(name: &[u8]) -> Option<fn(&u8) -> bool> {
+    Some(match name {
+        b"alpha" => u8::is_ascii_alphabetic,
+        b"upper" => u8::is_ascii_uppercase,
+        b"lower" => u8::is_ascii_lowercase,
+        b"digit" => u8::is_ascii_digit,
+        b"xdigit" => u8::is_ascii_hexdigit,
+        b"alnum" => u8::is_ascii_alphanumeric,
+        b"space" => |c: &u8| matches!(c, b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c'),
+        b"blank" => |c: &u8| matches!(c, b' ' | b'\t'),
+        b"punct" => u8::is_ascii_punctuation,
+        b"print" => |c: &u8| c.is_ascii_graphic() || *c == b' ',
+        b"graph" => u8::is_ascii_graphic,
+        b"cntrl" => u8::is_ascii_control,
+        _ => return None,
+    })
+}
+
+struct Parser<'p> {
+    pattern: &'p [u8],
+    pos: usize,
+    groups: usize,
+    closed: Vec<usize>,
+    backrefs: bool,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.pattern.get(self.pos).copied()
+    }
+
+    fn at(&self, text: &[u8]) -> bool {
+        self.pattern[self.pos..].starts_with(text)
+    }
+
+    fn alternation(&mut self, depth: usize) -> Result<Node, &'static str> {
+        let mut branches = vec![self.branch(depth)?];
+        while self.at(b"\\|") {
+            self.pos += 2;
+            branches.push(self.branch(depth)?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap_or(Node::Concat(Vec::new()))
+        } else {
+            Node::Alt(branches)
+        })
+    }
+
+    fn branch(&mut self, depth: usize) -> Result<Node, &'static str> {
+        let mut items: Vec<Node> = Vec::new();
+        // Whether a postfix operator here would have nothing to apply to
+        let mut bare = true;
+        while let Some(c) = self.peek() {
+            if self.at(b"\\|") || self.at(b"\\)") {
+                if self.at(b"\\)") && depth == 0 {
+                    return Err("Unmatched ) or \\)");
+                }
+                break;
+            }
+            self.pos += 1;
+            let item = match c {
+                b'^' if items.is_empty() => {
+                    items.push(Node::Assert(Assertion::LineStart));
+                    continue;
+                }
+                b'$' if self.peek().is_none() || self.at(b"\\)") || self.at(b"\\|") => {
+                    Node::Assert(Assertion::LineEnd)
+                }
+                b'*' | b'+' | b'?' if !bare => {
+                    let last = items.pop().ok_or("Invalid preceding regular expression")?;
+                    items.push(match c {
+                        b'*' => Node::Star(Box::new(last)),
+                        b'+' => Node::Plus(Box::new(last)),
+                        _ => Node::Optional(Box::new(last)),
+                    });
+                    continue;
+                }
+                b'.' => Node::Any,
+                b'[' => self.set()?,
+                b'\\' => self.escape(depth)?,
+                c => Node::Byte(c),
+            };
+            items.push(item);
+            bare = false;
+        }
+        Ok(Node::Concat(items))
+    }
+
+    fn escape(&mut self, depth: usize) -> Result<Node, &'static str> {
+        let c = self.peek().ok_or("Trailing backslash")?;
+        self.pos += 1;
+        Ok(match c {
+            b'(' => {
+                self.groups += 1;
+                let group = self.groups;
+                let inner = self.alternation(depth + 1)?;
+                if !self.at(b"\\)") {
+                    return Err("Unmatched ( or \\(");
+                }
+                self.pos += 2;
+                self.closed.push(group);
+                Node::Group(group, Box::new(inner))
+            }
+            b'1'..=b'9' => {
+                let group = usize::from(c - b'0');
+                if !self.closed.contains(&group) {
+                    return Err("Invalid back reference");
+                }
+                self.backrefs = true;
+                Node::Backref(group)
+            }
+            b'w' | b'W' => {
+                let mut set = [c == b'W'; 256];
+                for (byte, member) in (0..=u8::MAX).zip(set.iter_mut()) {
+                    if is_word(byte) {
+                        *member = c == b'w';
+                    }
+                }
+                Node::Set(Box::new(set))
+            }
+            b'b' => Node::Assert(Assertion::WordBoundary),
+            b'B' => Node::Assert(Assertion::NotWordBoundary),
+            b'<' => Node::Assert(Assertion::WordStart),
+            b'>' => Node::Assert(Assertion::WordEnd),
+            b'`' => Node::Assert(Assertion::TextStart),
+            b'\'' => Node::Assert(Assertion::TextEnd),
+            c => Node::Byte(c),
+        })
+    }
+
+    /// Parses a bracket expression; the `[` has been consumed.
+    fn set(&mut self) -> Result<Node, &'static str> {
+        const UNMATCHED: &str = "Unmatched [, [^, [:, [., or [=";
+        let negated = self.peek() == Some(b'^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut set = [false; 256];
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or(UNMATCHED)?;
+            self.pos += 1;
+            if c == b']' && !first {
+                break;
+            }
+            first = false;
+            if c == b'[' && self.peek() == Some(b':') {
+                let rest = &self.pattern[self.pos + 1..];
+                let len = rest.windows(2).position(|w| w == b":]").ok_or(UNMATCHED)?;
+                let test = class(&rest[..len]).ok_or("Invalid character class name")?;
+                for (byte, member) in (0..=u8::MAX).zip(set.iter_mut()) {
+                    *member |= test(&byte);
+                }
+                self.pos += len + 3;
+                continue;
+            }
+            let mut end = c;
+            if self.peek() == Some(b'-') && self.pattern.get(self.pos + 1) != Some(&b']') {
+                end = *self.pattern.get(self.pos + 1).ok_or(UNMATCHED)?;
+                self.pos += 2;
+                if end < c {
+                    return Err("Invalid range end");
+                }
+            }
+            for member in &mut set[usize::from(c)..=usize::from(end)] {
+                *member = true;
+            }
+        }
+        if negated {
+            for member in &mut set {
+                *member = !*member;
+            }
+        }
+        Ok(Node::Set(Box::new(set)))
+    }
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+    loops: usize,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> usize {
+        self.program.push(inst);
+        self.program.len() - 1
+    }
+
+    /// Fixes up the targets of a placeholder jump or split at `at`.
+    fn patch(&mut self, at: usize, inst: Inst) {
+        self.program[at] = inst;
+    }
+
+    fn compile(&mut self, node: &Node) {
+        match node {
+            Node::Byte(c) => {
+                self.emit(Inst::Byte(*c));
+            }
+            Node::Any => {
+                self.emit(Inst::Any);
+            }
+            Node::Set(set) => {
+                self.emit(Inst::Set(set.clone()));
+            }
+            Node::Assert(assertion) => {
+                self.emit(Inst::Assert(*assertion));
+            }
+            Node::Backref(group) => {
+                self.emit(Inst::Backref(*group));
+            }
+            Node::Group(group, inner) => {
+                self.emit(Inst::Save(group * 2));
+                self.compile(inner);
+                self.emit(Inst::Save(group * 2 + 1));
+            }
+            Node::Concat(items) => {
+                for item in items {
+                    self.compile(item);
+                }
+            }
+            Node::Alt(branches) => {
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 < branches.len() {
+                        let split = self.emit(Inst::Match);
+                        self.compile(branch);
+                        jumps.push(self.emit(Inst::Match));
+                        let next = self.program.len();
+                        self.patch(split, Inst::Split(split + 1, next));
+                    } else {
+                        self.compile(branch);
+                    }
+                }
+                let end = self.program.len();
+                for jump in jumps {
+                    self.patch(jump, Inst::Jump(end));
+                }
+            }
+            Node::Star(inner) => self.star(inner),
+            Node::Plus(inner) => {
+                self.compile(inner);
+                self.star(inner);
+            }
+            Node::Optional(inner) => {
+                let split = self.emit(Inst::Match);
+                self.compile(inner);
+                let end = self.program.len();
+                self.patch(split, Inst::Split(split + 1, end));
+            }
+        }
+    }
+
+    fn star(&mut self, inner: &Node) {
+        let register = self.loops;
+        self.loops += 1;
+        let split = self.emit(Inst::Match);
+        self.emit(Inst::Mark(register));
+        self.compile(inner);
+        self.emit(Inst::Progress(register));
+        self.emit(Inst::Jump(split));
+        let end = self.program.len();
+        self.patch(split, Inst::Split(split + 1, end));
+    }
+}
+
+/// The (instruction, position) states a match attempt has reached. It's
+/// allocated once per [`Searcher`] and reused for each start position: a
+/// state only counts as reached if it carries the current attempt's stamp.
+struct Visited {
+    stamps: Vec<u32>,
+    stamp: u32,
+}
+
+impl Visited {
+    /// A set for `regex` run over `text`; empty, and never reporting a
+    /// state as reached, if the program has back-references.
+    fn new(regex: &Regex, text: &[u8]) -> Self {
+        let len = if regex.backrefs {
+            0
+        } else {
+            regex.program.len() * (text.len() + 1)
+        };
+        Self {
+            stamps: vec![0; len],
+            stamp: 0,
+        }
+    }
+
+    /// Forgets every state, for a new attempt.
+    fn clear(&mut self) {
+        if self.stamp == u32::MAX {
+            self.stamps.fill(0);
+            self.stamp = 0;
+        }
+        self.stamp += 1;
+    }
+
+    /// Records state `index`, returning whether it was reached before.
+    fn reached(&mut self, index: usize) -> bool {
+        match self.stamps.get_mut(index) {
+            Some(stamp) if *stamp == self.stamp => true,
+            Some(stamp) => {
+                *stamp = self.stamp;
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// Repeated searches of one text, sharing the memory of reached states.
+pub struct Searcher<'a> {
+    regex: &'a Regex,
+    text: &'a [u8],
+    visited: Visited,
+}
+
+impl Searcher<'_> {
+    /// Finds the leftmost match starting at or after `start`.
+    pub fn search(&mut self, start: usize) -> Option<Captures> {
+        (start..=self.text.len())
+            .find_map(|at| self.regex.match_from(self.text, at, &mut self.visited))
+    }
+}
+
+/// Undo records for the backtracking search.
+enum Job {
+    Explore(usize, usize),
+    RestoreSlot(usize, Option<usize>),
+    RestoreMark(usize, usize),
+}
+
+impl Regex {
+    /// Compiles `pattern`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with GNU regex's message for a malformed pattern.
+    pub fn new(pattern: &[u8]) -> Result<Self, &'static str> {
+        let mut parser = Parser {
+            pattern,
+            pos: 0,
+            groups: 0,
+            closed: Vec::new(),
+            backrefs: false,
+        };
+        let node = parser.alternation(0)?;
+        let mut compiler = Compiler {
+            program: Vec::new(),
+            loops: 0,
+        };
+        compiler.compile(&node);
+        compiler.emit(Inst::Match);
+        Ok(Self {
+            program: compiler.program,
+            groups: parser.groups,
+            loops: compiler.loops,
+            backrefs: parser.backrefs,
+        })
+    }
+
+    /// The number of `\(` groups.
+    pub const fn groups(&self) -> usize {
+        self.groups
+    }
+
+    /// A searcher for matches in `text`, for finding several of them.
+    pub fn searcher<'a>(&'a self, text: &'a [u8]) -> Searcher<'a> {
+        Searcher {
+            regex: self,
+            text,
+            visited: Visited::new(self, text),
+        }
+    }
+
+    /// Finds the leftmost match in `text` starting at or after `start`.
+    pub fn search(&self, text: &[u8], start: usize) -> Option<Captures> {
+        self.searcher(text).search(start)
+    }
+
+    /// The longest match that starts exactly at `at`.
+    pub fn match_at(&self, text: &[u8], at: usize) -> Option<Captures> {
+        self.match_from(text, at, &mut Visited::new(self, text))
+    }
+
+    /// Like [`match_at`](Self::match_at), reusing `visited` from an earlier
+    /// attempt on the same text.
+    #[allow(clippy::too_many_lines)]
+    fn match_from(&self, text: &[u8], at: usize, visited: &mut Visited) -> Option<Captures> {
+        let mut slots = vec![None; (self.groups + 1) * 2];
+        let mut marks = vec![usize::MAX; self.loops];
+        // Without back-references, reaching the same instruction at the same
+        // position again can't lead anywhere new
+        visited.clear();
+        let mut best: Option<(usize, Vec<Option<usize>>)> = None;
+        let mut jobs = vec![Job::Explore(0, at)];
+        while let Some(job) = jobs.pop() {
+            let (mut pc, mut pos) = match job {
+                Job::Explore(pc, pos) => (pc, pos),
+                Job::RestoreSlot(slot, old) => {
+                    slots[slot] = old;
+                    continue;
+                }
+                Job::RestoreMark(register, old) => {
+                    marks[register] = old;
+                    continue;
+                }
+            };
+            loop {
+                if visited.reached(pc * (text.len() + 1) + pos) {
+                    break;
+                }
+                match &self.program[pc] {
+                    Inst::Byte(c) => {
+                        if text.get(pos) != Some(c) {
+                            break;
+                        }
+                        pos += 1;
+                    }
+                    Inst::Any => {
+                        if matches!(text.get(pos), None | Some(b'\n')) {
+                            break;
+                        }
+                        pos += 1;
+                    }
+                    Inst::Set(set) => match text.get(pos) {
+                        Some(&c) if set[usize::from(c)] => pos += 1,
+                        _ => break,
+                    },
+                    Inst::Assert(assertion) => {
+                        if !Self::holds(*assertion, text, pos) {
+                            break;
+                        }
+                    }
+                    Inst::Backref(group) => {
+                        let (Some(start), Some(end)) = (slots[group * 2], slots[group * 2 + 1])
+                        else {
+                            break;
+                        };
+                        let len = end - start;
+                        if !text[pos..].starts_with(&text[start..end]) {
+                            break;
+                        }
+                        pos += len;
+                    }
+                    Inst::Save(slot) => {
+                        jobs.push(Job::RestoreSlot(*slot, slots[*slot]));
+                        slots[*slot] = Some(pos);
+                    }
+                    Inst::Split(first, second) => {
+                        jobs.push(Job::Explore(*second, pos));
+                        pc = *first;
+                        continue;
+                    }
+                    Inst::Jump(target) => {
+                        pc = *target;
+                        continue;
+                    }
+                    Inst::Mark(register) => {
+                        jobs.push(Job::RestoreMark(*register, marks[*register]));
+                        marks[*register] = pos;
+                    }
+                    Inst::Progress(register) => {
+                        if marks[*register] == pos {
+                            break;
+                        }
+                    }
+                    Inst::Match => {
+                        if best.as_ref().is_none_or(|(end, _)| pos > *end) {
+                            best = Some((pos, slots.clone()));
+                        }
+                        if pos == text.len() {
+                            jobs.clear();
+                        }
+                        break;
+                    }
+                }
+                pc += 1;
+            }
+        }
+        let (end, slots) = best?;
+        let mut captures = vec![Some((at, end))];
+        captures.extend(
+            slots
+                .chunks(2)
+                .skip(1)
+                .map(|pair| Some((pair[0]?, pair[1]?))),
+        );
+        Some(captures)
+    }
+
+    fn holds(assertion: Assertion, text: &[u8], pos: usize) -> bool {
+        let before = pos.checked_sub(1).map(|i| text[i]);
+        let after = text.get(pos).copied();
+        let word_before = before.is_some_and(is_word);
+        let word_after = after.is_some_and(is_word);
+        match assertion {
+            Assertion::LineStart => matches!(before, None | Some(b'\n')),
+            Assertion::LineEnd => matches!(after, None | Some(b'\n')),
+            Assertion::TextStart => pos == 0,
+            Assertion::TextEnd => pos == text.len(),
+            Assertion::WordBoundary => word_before != word_after,
+            Assertion::NotWordBoundary => word_before == word_after,
+            Assertion::WordStart => !word_before && word_after,
+            Assertion::WordEnd => word_before && !word_after,
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use crate::engine::MacroValue;
+
+/// Macro definitions, keyed by name. Each name holds a stack of definitions:
+/// `pushdef` shadows the visible one and `popdef` brings it back.
+pub struct SymbolTable {
+    entries: HashMap<String, Vec<MacroValue>>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The visible definition of `name`.
+    pub fn lookup(&self, name: &str) -> Option<&MacroValue> {
+        self.entries.get(name).and_then(|defs| defs.last())
+    }
+
+    /// Replaces the visible definition of `name`, or creates one.
+    pub fn define(&mut self, name: &str, value: MacroValue) {
+        let defs = self.entries.entry(name.into()).or_default();
+        defs.pop();
+        defs.push(value);
+    }
+
+    /// Shadows the visible definition of `name` with `value`.
+    pub fn pushdef(&mut self, name: &str, value: MacroValue) {
+        self.entries.entry(name.into()).or_default().push(value);
+    }
+
+    /// Removes the visible definition of `name`, revealing the one below.
+    pub fn popdef(&mut self, name: &str) {
+        if let Some(defs) = self.entries.get_mut(name) {
+            defs.pop();
+            if defs.is_empty() {
+                self.entries.remove(name);
+            }
+        }
+    }
+
+    /// Removes every definition of `name`.
+    pub fn undefine(&mut self, name: &str) {
+        self.entries.remove(name);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Every defined name, sorted, with its visible definition and the
+    /// number of definitions stacked under it.
+    pub fn sorted(&self) -> Vec<(&str, &MacroValue, usize)> {
+        let mut defs: Vec<_> = self
+            .entries
+            .iter()
+            .filter_map(|(name, defs)| Some((&name[..], defs.last()?, defs.len())))
+            .collect();
+        defs.sort_unstable_by_key(|def| def.0);
+        defs
+    }
+}
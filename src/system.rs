@@ -0,0 +1,156 @@
+//! Builtins that reach outside m4: shell commands and temporary files.
+
+use std::collections::hash_map::RandomState;
+use std::fs::{DirBuilder, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind};
+use std::process::{self, Command, ExitStatus, Stdio};
+
+use crate::engine::{os_string_of, Engine};
+use crate::error::{os_reason, Error};
+
+/// The shell commands run through when none was configured.
+const DEFAULT_SHELL: &str = "/bin/sh";
+
+/// How many names `mkstemp` and `mkdtemp` try before giving up.
+const TEMP_ATTEMPTS: u32 = 100;
+
+/// Characters that replace the `X`s of a temporary file template.
+const TEMP_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// `maketemp` as POSIX describes it, touching nothing: the trailing `X`s
+/// become the process ID, zero-padded or cut down to their number.
+pub fn traditional_maketemp(template: &str) -> String {
+    let stem = template.trim_end_matches('X');
+    // The first character stays even if it's an X
+    let stem = if stem.is_empty() {
+        &template[..template.len().min(1)]
+    } else {
+        stem
+    };
+    let width = template.len() - stem.len();
+    let pid = process::id().to_string();
+    let pid = &pid[pid.len().saturating_sub(width)..];
+    format!("{stem}{pid:0>width$}")
+}
+
+/// The value `sysval` gives for `status`: the exit code, or for a command
+/// killed by a signal, the signal number times 256, as in GNU m4.
+fn status_value(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return signal << 8;
+        }
+    }
+    status.code().unwrap_or(127)
+}
+
+impl Engine<'_> {
+    /// A command that runs `command` through the configured shell.
+    fn shell_command(&self, command: &str) -> Command {
+        let mut shell = Command::new(self.options.shell.as_deref().unwrap_or(DEFAULT_SHELL));
+        shell.arg("-c").arg(os_string_of(command));
+        shell
+    }
+
+    /// `syscmd(command)`: runs `command` with m4's own standard streams.
+    /// What was output so far is flushed first, so the command's output
+    /// lands after it.
+    pub(crate) fn syscmd(&mut self, command: &str) -> Result<(), Error> {
+        self.output.flush()?;
+        self.diagnostics.flush()?;
+        self.sysval = match self.shell_command(command).status() {
+            Ok(status) => status_value(status),
+            Err(e) => {
+                let reason = os_reason(&e);
+                self.warn(&format!("cannot run command `{command}': {reason}"))?;
+                127
+            }
+        };
+        Ok(())
+    }
+
+    /// `esyscmd(command)`: runs `command` and rescans what it writes to
+    /// stdout. Its stderr is still m4's.
+    pub(crate) fn esyscmd(&mut self, command: &str) -> Result<(), Error> {
+        self.diagnostics.flush()?;
+        let output = self
+            .shell_command(command)
+            .stderr(Stdio::inherit())
+            .output();
+        self.sysval = match output {
+            Ok(output) => {
+                self.input.push(output.stdout);
+                status_value(output.status)
+            }
+            Err(e) => {
+                let reason = os_reason(&e);
+                self.warn(&format!("cannot run command `{command}': {reason}"))?;
+                127
+            }
+        };
+        Ok(())
+    }
+
+    /// `mkstemp(template)`: creates a new file only this user can read,
+    /// named by `template` with its trailing `X`s, at least six of them,
+    /// replaced by random characters. Returns the name, or `None` after
+    /// reporting why no file could be made.
+    pub(crate) fn mkstemp(&mut self, template: &str) -> Result<Option<String>, Error> {
+        self.make_temp(template, "file", |name| {
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            options.open(os_string_of(name)).map(drop)
+        })
+    }
+
+    /// `mkdtemp(template)`: like [`mkstemp`](Self::mkstemp), but creates a
+    /// directory only this user can enter.
+    pub(crate) fn mkdtemp(&mut self, template: &str) -> Result<Option<String>, Error> {
+        self.make_temp(template, "directory", |name| {
+            let mut builder = DirBuilder::new();
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+            builder.create(os_string_of(name))
+        })
+    }
+
+    /// Tries random names from `template` until `create` makes a `kind` of
+    /// thing that wasn't there yet.
+    fn make_temp(
+        &mut self,
+        template: &str,
+        kind: &str,
+        create: impl Fn(&str) -> io::Result<()>,
+    ) -> Result<Option<String>, Error> {
+        let stem = template.trim_end_matches('X');
+        let width = (template.len() - stem.len()).max(6);
+        let mut error = None;
+        for _ in 0..TEMP_ATTEMPTS {
+            let mut hasher = RandomState::new().build_hasher();
+            let mut name = String::from(stem);
+            for _ in 0..width {
+                hasher.write_u32(process::id());
+                let index = usize::try_from(hasher.finish() % TEMP_CHARS.len() as u64);
+                name.push(char::from(TEMP_CHARS[index.unwrap_or(0)]));
+            }
+            match create(&name) {
+                Ok(()) => return Ok(Some(name)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+        let reason = error.map_or_else(|| "too many attempts".into(), |e| os_reason(&e));
+        self.error(&format!(
+            "cannot create {kind} from template `{template}': {reason}"
+        ))?;
+        Ok(None)
+    }
+}
@@ -0,0 +1,134 @@
+//! Trace output for macro calls, as selected with `--trace`, `traceon` and
+//! the `t` debug flag.
+
+use std::fs::OpenOptions;
+use std::io;
+
+use crate::engine::{bytes_of, os_string_of, Engine, MacroValue};
+use crate::error::{os_reason, Error, Sink, Stream};
+use crate::input::Location;
+
+/// The debug flags `V` stands for: all of them.
+const ALL_DEBUG_FLAGS: &str = "acefilpqtx";
+
+/// The flags an empty `debugmode` argument restores.
+const DEFAULT_DEBUG_FLAGS: &str = "aeq";
+
+impl Engine<'_> {
+    /// Whether calls to `name` are traced.
+    pub(crate) fn is_traced(&self, name: &str) -> bool {
+        self.debug_flags.contains('t') || self.traced.contains(name)
+    }
+
+    /// Quotes `text` for a trace line if the `q` debug flag is set.
+    fn trace_quote(&self, text: &str) -> String {
+        if self.debug_flags.contains('q') {
+            format!(
+                "{}{text}{}",
+                self.delimiters.quote_start, self.delimiters.quote_end
+            )
+        } else {
+            text.into()
+        }
+    }
+
+    /// Writes the trace line for a finished call to `name` with `args`, in
+    /// GNU m4's format, placing it at `location` where the call started. Its
+    /// expansion is whatever the call queued as input above `mark`.
+    pub(crate) fn trace_call(
+        &mut self,
+        name: &str,
+        location: &Location,
+        args: &[MacroValue],
+        mark: usize,
+    ) -> Result<(), Error> {
+        let flags = &self.debug_flags;
+        let file = if flags.contains('f') {
+            format!("{}:", location.file)
+        } else {
+            String::new()
+        };
+        let line = if flags.contains('l') {
+            format!("{}:", location.line)
+        } else {
+            String::new()
+        };
+        let args = if flags.contains('a') && !args.is_empty() {
+            let args: Vec<String> = args
+                .iter()
+                .map(|arg| match arg {
+                    MacroValue::Text(text) => self.trace_quote(text),
+                    MacroValue::BuiltinFunction(builtin) => format!("<{}>", builtin.name()),
+                })
+                .collect();
+            format!("({})", args.join(", "))
+        } else {
+            String::new()
+        };
+        let expansion = if flags.contains('e') {
+            format!(" -> {}", self.trace_quote(&self.input.pushed_since(mark)))
+        } else {
+            String::new()
+        };
+        let level = self.expansion_level;
+        let line = format!("m4trace:{file}{line} -{level}- {name}{args}{expansion}\n");
+        self.debug_output.write(&bytes_of(&line))
+    }
+
+    /// `debugmode([flags])`: replaces the debug flags, or with a leading
+    /// `+` or `-` adds or removes some. No argument clears them all and an
+    /// empty one restores the defaults.
+    pub(crate) fn debugmode(&mut self, args: &[MacroValue]) -> Result<(), Error> {
+        let Some(arg) = args.first() else {
+            self.debug_flags.clear();
+            return Ok(());
+        };
+        let text = arg.text();
+        let (change, letters) = match text.as_bytes().first() {
+            Some(&sign @ (b'+' | b'-')) => (Some(sign), &text[1..]),
+            _ => (None, text),
+        };
+        if !letters
+            .chars()
+            .all(|c| c == 'V' || ALL_DEBUG_FLAGS.contains(c))
+        {
+            return self.warn(&format!("bad debug flags: `{text}'"));
+        }
+        let letters = letters.replace('V', ALL_DEBUG_FLAGS);
+        self.debug_flags = match change {
+            Some(b'+') => self.debug_flags.chars().chain(letters.chars()).collect(),
+            Some(_) => self
+                .debug_flags
+                .chars()
+                .filter(|&c| !letters.contains(c))
+                .collect(),
+            None if text.is_empty() => DEFAULT_DEBUG_FLAGS.into(),
+            None => letters,
+        };
+        Ok(())
+    }
+
+    /// `debugfile([file])`: appends debug and trace output to `file` from
+    /// now on. An empty name means stderr again, and no argument at all
+    /// discards the output.
+    pub(crate) fn debugfile(&mut self, args: &[MacroValue]) -> Result<(), Error> {
+        let sink: Box<dyn io::Write> = match args.first().map(MacroValue::text) {
+            None => Box::new(io::sink()),
+            Some("") => Box::new(io::stderr()),
+            Some(path) => match OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(os_string_of(path))
+            {
+                Ok(file) => Box::new(file),
+                Err(e) => {
+                    let reason = os_reason(&e);
+                    return self.warn(&format!("cannot set debug file `{path}': {reason}"));
+                }
+            },
+        };
+        self.debug_output.flush()?;
+        self.debug_output = Sink::new(sink, Stream::Debug);
+        Ok(())
+    }
+}
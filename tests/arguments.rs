@@ -0,0 +1,66 @@
+//! Collecting the arguments of `name(arg1, arg2, ...)` calls.
+
+mod common;
+
+use common::{m4, m4_warnings};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn arguments_split_on_unquoted_commas() {
+    assert_eq!(m4("len(a,b) len(`a,b')"), "1 3");
+}
+
+#[test]
+fn nested_parentheses_keep_their_commas() {
+    assert_eq!(m4("len((a,b)) len(`(')"), "5 1");
+}
+
+#[test]
+fn leading_unquoted_whitespace_is_skipped() {
+    assert_eq!(m4("len(\n\t a ) len(` 'a)"), "2 2");
+    assert_eq!(m4("ifelse(a, \n  a,yes,no)"), "yes");
+}
+
+#[test]
+fn quotes_nest_and_lose_one_level() {
+    assert_eq!(m4("`a`b'c' len(``ab'')"), "a`b'c 4");
+}
+
+#[test]
+fn end_of_file_in_a_string_is_an_error() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "a\n`b\nc");
+    assert_eq!(output, "a\nb\nc");
+    assert_eq!(warnings, "m4:stdin:2: end of file in string\n");
+}
+
+#[test]
+fn only_an_immediate_parenthesis_starts_arguments() {
+    let input = "define(`f', `[$#:$1]')f(x) f (x) f\n(x) f`'(x)";
+    assert_eq!(m4(input), "[1:x] [0:] (x) [0:]\n(x) [0:](x)");
+}
+
+#[test]
+fn the_parenthesis_may_follow_an_expansion() {
+    assert_eq!(m4("define(`f', `[$#:$1]')define(`g', `f')g()(x)"), "[1:x]");
+}
+
+#[test]
+fn arguments_are_expanded_before_substitution() {
+    assert_eq!(
+        m4("define(`x', `X,Y')define(`f', `[$2|$3]')f(x, `x')"),
+        "[Y|X,Y]"
+    );
+}
+
+#[test]
+fn argument_side_effects_happen_before_the_body_is_read() {
+    assert_eq!(m4("define(`f', `$1 y')f(define(`y', `set')y)"), "set set");
+}
+
+#[test]
+fn substituted_arguments_are_not_expanded_twice() {
+    assert_eq!(
+        m4("define(`a', `b')define(`f', `$1')f(`a') f(``a'')"),
+        "b a"
+    );
+}
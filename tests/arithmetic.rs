@@ -0,0 +1,179 @@
+mod common;
+
+use common::{m4, m4_warnings};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn incr_and_decr_step_by_one() {
+    assert_eq!(m4("incr(41) decr(43) decr(0) incr(-5)"), "42 42 -1 -4");
+}
+
+#[test]
+fn incr_result_is_rescanned() {
+    assert_eq!(m4("changeword(`[a-z0-9]+')define(1,one)incr(0)"), "one");
+}
+
+#[test]
+fn non_numeric_incr_expands_to_nothing() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "a incr(x) decr(1x) b");
+    assert_eq!(output, "a   b");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: non-numeric argument to builtin `incr'\n\
+         m4:stdin:1: Warning: non-numeric argument to builtin `decr'\n"
+    );
+}
+
+#[test]
+fn empty_incr_is_zero_with_a_warning() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "incr()");
+    assert_eq!(output, "1");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: empty string treated as 0 in builtin `incr'\n"
+    );
+}
+
+#[test]
+fn incr_and_decr_wrap_like_c_ints() {
+    assert_eq!(
+        m4("incr(2147483647) decr(-2147483648)"),
+        "-2147483648 2147483647"
+    );
+    assert_eq!(m4("incr(4294967296)"), "1");
+}
+
+#[test]
+fn eval_follows_c_precedence() {
+    let cases = [
+        ("1+2*3", "7"),
+        ("(1+2)*3", "9"),
+        ("-7/2", "-3"),
+        ("-7%3", "-1"),
+        ("~0 !5 !0", "-1 0 1"),
+        ("1<<4>>2", "4"),
+        ("1<2==2>1", "1"),
+        ("1|2^3&4", "3"),
+        ("1&&0||1", "1"),
+        ("2**3**2", "512"),
+        ("-2**2", "4"),
+        ("2*3**2", "18"),
+    ];
+    for (expression, value) in cases {
+        // Each space-separated part is its own eval
+        let input: Vec<String> = expression
+            .split(' ')
+            .map(|part| format!("eval({part})"))
+            .collect();
+        assert_eq!(m4(&input.join(" ")), value, "{expression}");
+    }
+}
+
+#[test]
+fn eval_wraps_around_at_32_bits() {
+    assert_eq!(m4("eval(2147483647+1)"), "-2147483648");
+    assert_eq!(m4("eval(-2147483648/-1)"), "-2147483648");
+    assert_eq!(m4("eval(1<<32) eval(1<<31)"), "1 -2147483648");
+    assert_eq!(m4("eval(-8>>1)"), "-4");
+}
+
+#[test]
+fn eval_radix_and_width_are_c_ints() {
+    assert_eq!(m4("eval(10,4294967312,4294967298)"), "0a");
+}
+
+#[test]
+fn eval_short_circuits() {
+    assert_eq!(m4("eval(0&&1/0) eval(1||1%0)"), "0 1");
+}
+
+#[test]
+fn eval_errors_expand_to_nothing() {
+    let cases = [
+        ("1/0", "divide by zero in eval: 1/0"),
+        ("1%0", "modulo by zero in eval: 1%0"),
+        ("2**-1", "negative exponent in eval: 2**-1"),
+        ("1+", "bad expression in eval: 1+"),
+        ("1 2", "bad expression in eval (excess input): 1 2"),
+        ("a", "bad expression in eval (bad input): a"),
+        ("1=1", "invalid operator in eval: 1=1"),
+    ];
+    for (expression, message) in cases {
+        let input = format!("eval({expression})");
+        let (output, warnings) = m4_warnings(EngineBuilder::new(), &input);
+        assert_eq!(output, "", "{expression}");
+        assert_eq!(warnings, format!("m4:stdin:1: {message}\n"));
+    }
+}
+
+#[test]
+fn empty_eval_is_zero_with_a_warning() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "eval() eval(`', 2, 4)");
+    assert_eq!(output, "0 0000");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: empty string treated as 0 in builtin `eval'\n".repeat(2)
+    );
+}
+
+#[test]
+fn eval_renders_in_radix_and_width() {
+    assert_eq!(m4("eval(255,16) eval(35,36) eval(5,2,8)"), "ff z 00000101");
+    assert_eq!(m4("eval(-255,16) eval(-5,10,3)"), "-ff -005");
+    assert_eq!(
+        m4("eval(3,1) eval(-2,1) eval(0,1) eval(2,1,4)"),
+        "111 -11 0 0011"
+    );
+    assert_eq!(m4("eval(7,,3) eval(0,10,0) eval(0,1,0)"), "007 0 ");
+    assert_eq!(m4("eval(-2147483648,2)"), format!("-1{}", "0".repeat(31)));
+}
+
+#[test]
+fn eval_rejects_bad_radix_and_width() {
+    let cases = [
+        (
+            "eval(1,37)",
+            "radix in builtin `eval' out of range (radix = 37)",
+        ),
+        (
+            "eval(1,0)",
+            "radix in builtin `eval' out of range (radix = 0)",
+        ),
+        ("eval(1,10,-1)", "negative width to builtin `eval'"),
+    ];
+    for (input, message) in cases {
+        let (output, warnings) = m4_warnings(EngineBuilder::new(), input);
+        assert_eq!(output, "", "{input}");
+        assert_eq!(warnings, format!("m4:stdin:1: Warning: {message}\n"));
+    }
+}
+
+#[test]
+fn eval_reads_literals_in_other_bases() {
+    assert_eq!(
+        m4("eval(0x1F) eval(0XfF) eval(017) eval(0b101) eval(0B11)"),
+        "31 255 15 5 3"
+    );
+    assert_eq!(
+        m4("eval(0r36:zz) eval(0r2:110) eval(0r1:0111) eval(0R16:Ab)"),
+        "1295 6 3 171"
+    );
+    assert_eq!(m4("eval(0) eval(00) eval(10+0x10+010)"), "0 0 34");
+    assert_eq!(m4("eval(0xffffffff)"), "-1");
+}
+
+#[test]
+fn eval_rejects_malformed_literals() {
+    let cases = [
+        ("09", "bad expression in eval (excess input): 09"),
+        ("0r37:1", "bad expression in eval (bad input): 0r37:1"),
+        ("0r:1", "bad expression in eval (bad input): 0r:1"),
+        ("0r10", "bad expression in eval (bad input): 0r10"),
+    ];
+    for (expression, message) in cases {
+        let input = format!("eval({expression})");
+        let (output, warnings) = m4_warnings(EngineBuilder::new(), &input);
+        assert_eq!(output, "", "{expression}");
+        assert_eq!(warnings, format!("m4:stdin:1: {message}\n"));
+    }
+}
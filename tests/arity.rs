@@ -0,0 +1,81 @@
+mod common;
+
+use common::m4_warnings;
+use lc_m4::EngineBuilder;
+
+fn run(input: &str) -> (String, String) {
+    m4_warnings(EngineBuilder::new(), input)
+}
+
+#[test]
+fn excess_arguments_are_ignored_with_a_warning() {
+    assert_eq!(
+        run("define(x,y,z)x"),
+        (
+            "y".into(),
+            "m4:stdin:1: Warning: excess arguments to builtin `define' ignored\n".into()
+        )
+    );
+    assert_eq!(
+        run("divnum()"),
+        (
+            "0".into(),
+            "m4:stdin:1: Warning: excess arguments to builtin `divnum' ignored\n".into()
+        )
+    );
+}
+
+#[test]
+fn too_few_arguments_skip_the_call() {
+    assert_eq!(
+        run("ifdef(x)"),
+        (
+            String::new(),
+            "m4:stdin:1: Warning: too few arguments to builtin `ifdef'\n".into()
+        )
+    );
+    assert_eq!(
+        run("ifelse(a,b)"),
+        (
+            String::new(),
+            "m4:stdin:1: Warning: too few arguments to builtin `ifelse'\n".into()
+        )
+    );
+}
+
+#[test]
+fn some_builtins_still_expand_with_one_argument() {
+    let warnings =
+        |name: &str| format!("m4:stdin:1: Warning: too few arguments to builtin `{name}'\n");
+    assert_eq!(run("index(abc)"), ("0".into(), warnings("index")));
+    assert_eq!(run("regexp(abc)"), ("0".into(), warnings("regexp")));
+    assert_eq!(run("substr(abc)"), ("abc".into(), warnings("substr")));
+    assert_eq!(run("translit(abc)"), ("abc".into(), warnings("translit")));
+    assert_eq!(run("patsubst(abc)"), ("abc".into(), warnings("patsubst")));
+}
+
+#[test]
+fn ifelse_warns_when_an_argument_is_left_over() {
+    let excess = "m4:stdin:1: Warning: excess arguments to builtin `ifelse' ignored\n";
+    assert_eq!(run("ifelse(a,b,c,d,e)"), ("d".into(), excess.into()));
+    assert_eq!(run("ifelse(a,b,c,d,e,f,g,h)"), ("g".into(), excess.into()));
+    for input in ["ifelse(a,b,c)", "ifelse(a,b,c,d)", "ifelse(a,b,c,d,e,f,g)"] {
+        assert_eq!(run(input).1, "", "{input}");
+    }
+}
+
+#[test]
+fn ifelse_with_one_argument_is_a_silent_comment() {
+    assert_eq!(run("ifelse(anything)"), (String::new(), String::new()));
+}
+
+#[test]
+fn builtin_checks_the_arity_of_its_target() {
+    assert_eq!(
+        run("builtin(ifdef)"),
+        (
+            String::new(),
+            "m4:stdin:1: Warning: too few arguments to builtin `ifdef'\n".into()
+        )
+    );
+}
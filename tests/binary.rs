@@ -0,0 +1,60 @@
+//! Text outside of macros is copied byte for byte, whatever the bytes are.
+
+mod common;
+
+use std::fs;
+
+use common::{m4, m4_warnings, temp_file};
+use lc_m4::EngineBuilder;
+
+fn m4_bytes(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut engine = EngineBuilder::new().output(&mut out).build().unwrap();
+    engine.process(&mut &input[..]).unwrap();
+    engine.finish().unwrap();
+    drop(engine);
+    out
+}
+
+#[test]
+fn arbitrary_bytes_pass_through() {
+    let input = b"a\0b\xff\x80 \xc3\xa9\r\n";
+    assert_eq!(m4_bytes(input), input);
+}
+
+#[test]
+fn bytes_survive_macros_and_diversions() {
+    assert_eq!(
+        m4_bytes(b"define(`x', `\0\xfe')divert(1)x\xe9divert(0)len(`\xc3\xa9')x"),
+        b"2\0\xfe\0\xfe\xe9"
+    );
+}
+
+#[test]
+fn utf8_survives_warnings() {
+    let (out, warnings) = m4_warnings(
+        EngineBuilder::new().program_name("m4é"),
+        "errprint(`héllo\n')eval(`1é')",
+    );
+    assert_eq!(out, "");
+    assert!(warnings.starts_with("héllo\nm4é:"), "{warnings}");
+    assert!(warnings.contains("1é"), "{warnings}");
+}
+
+#[test]
+fn utf8_file_names_are_opened() {
+    let path = temp_file("included-ü", "from ü\n");
+    let out = m4(&format!("include(`{}')", path.to_str().unwrap()));
+    fs::remove_file(path).unwrap();
+    assert_eq!(out, "from ü\n");
+}
+
+#[test]
+fn nul_in_a_definition_is_kept_with_a_warning() {
+    let (out, warnings) = m4_warnings(EngineBuilder::new(), "define(`n', `a\0b')n");
+    assert_eq!(out, "a\0b");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: definition of `n' contains a NUL byte\n"
+    );
+}
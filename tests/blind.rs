@@ -0,0 +1,37 @@
+//! Blind builtins are only called when a parenthesis follows their name.
+
+mod common;
+
+use common::{m4, m4_with};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn blind_builtins_pass_through_without_arguments() {
+    assert_eq!(
+        m4("define ifelse len incr eval translit"),
+        "define ifelse len incr eval translit"
+    );
+}
+
+#[test]
+fn blind_builtins_are_called_with_parentheses() {
+    assert_eq!(m4("len() incr(1)"), "0 2");
+}
+
+#[test]
+fn other_builtins_are_called_without_arguments() {
+    assert_eq!(m4("divnum sysval dnl gone\nkept"), "0 0 kept");
+}
+
+#[test]
+fn blindness_follows_the_builtin_not_the_name() {
+    assert_eq!(m4("define(size,defn(len))size size(ab)"), "size 2");
+}
+
+#[test]
+fn traditional_mode_builtins_are_blind_too() {
+    assert_eq!(
+        m4_with(EngineBuilder::new().traditional(), "define len"),
+        "define len"
+    );
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::{m4, m4_frozen, m4_warnings, m4_with};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn builtin_calls_by_name() {
+    assert_eq!(m4("builtin(`len', `abc') builtin(`divnum')"), "3 0");
+}
+
+#[test]
+fn builtin_reaches_undefined_and_shadowed_builtins() {
+    assert_eq!(
+        m4("undefine(`len')len(abc) builtin(`len', abc)"),
+        "len(abc) 3"
+    );
+    assert_eq!(m4("define(`len', no)len(abc) builtin(`len', abc)"), "no 3");
+}
+
+#[test]
+fn builtin_rejects_unknown_names() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "builtin(nope,x)");
+    assert_eq!(output, "");
+    assert_eq!(warnings, "m4:stdin:1: Warning: undefined builtin `nope'\n");
+}
+
+#[test]
+fn builtin_respects_safer_mode() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new().safer(true), "builtin(syscmd,true)");
+    assert_eq!(output, "");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: undefined builtin `syscmd'\n"
+    );
+}
+
+#[test]
+fn builtin_is_gnu_only() {
+    assert_eq!(
+        m4_with(EngineBuilder::new().traditional(), "builtin(x)"),
+        "builtin(x)"
+    );
+}
+
+#[test]
+fn defn_copies_survive_undefine() {
+    assert_eq!(
+        m4("define(size,defn(len))undefine(len)len(abc) size(abc)"),
+        "len(abc) 3"
+    );
+    assert_eq!(
+        m4("define(saved,defn(len))undefine(len)define(len,defn(saved))len(ab)"),
+        "2"
+    );
+}
+
+#[test]
+fn renamed_builtins_warn_under_their_own_name() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "define(step,defn(incr))step(1,2)");
+    assert_eq!(output, "2");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: excess arguments to builtin `incr' ignored\n"
+    );
+}
+
+#[test]
+fn builtin_tokens_pass_through_the_input() {
+    assert_eq!(m4("pushdef(`step', defn(`incr'))step(1)"), "2");
+    assert_eq!(m4("[defn(`len')]"), "[]");
+    assert_eq!(m4("define(`l', `x'defn(`len'))l(abc)"), "x");
+}
+
+#[test]
+fn frozen_builtins_are_called_from_expansions() {
+    assert_eq!(
+        m4_frozen("frozen.m4f", "define(`d', `define(`x', `y')')d x"),
+        " y"
+    );
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::{m4, m4_cli, m4_warnings, m4_with};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn changeword_limits_macro_names_to_the_regexp() {
+    assert_eq!(m4("divert_x"), "divert_x");
+    assert_eq!(m4("changeword([a-z]+)divert_x"), "_x");
+}
+
+#[test]
+fn first_group_is_the_name_looked_up() {
+    assert_eq!(
+        m4("changeword(@\\([a-z]*\\))@divert divert @nope"),
+        " divert @nope"
+    );
+}
+
+#[test]
+fn empty_regexp_restores_default_scanning() {
+    assert_eq!(m4("changeword([a-z]+)changeword()divert_x"), "divert_x");
+}
+
+#[test]
+fn word_regexp_option_applies_from_the_start() {
+    let builder = EngineBuilder::new().word_regexp("[a-z]+");
+    assert_eq!(m4_with(builder, "divert_x"), "_x");
+}
+
+#[test]
+fn bad_regexp_is_reported_and_ignored() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "changeword([a-z)divert_x");
+    assert_eq!(output, "divert_x");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: bad regular expression `[a-z': Unmatched [, [^, [:, [., or [=\n"
+    );
+}
+
+#[test]
+fn changeword_needs_gnu_mode() {
+    let builder = EngineBuilder::new().traditional();
+    assert_eq!(m4_with(builder, "changeword"), "changeword");
+}
+
+#[test]
+fn word_regexp_flag_sets_the_regexp() {
+    let (output, _, status) = m4_cli(&["--word-regexp=[a-z]+"], "divert_x");
+    assert_eq!((status, output.as_str()), (Some(0), "_x"));
+}
+
+#[test]
+fn bad_word_regexp_flag_fails() {
+    let (output, errors, status) = m4_cli(&["--word-regexp=[a-z"], "");
+    assert_eq!((status, output.as_str()), (Some(1), ""));
+    assert!(errors.contains("bad regular expression `[a-z'"));
+}
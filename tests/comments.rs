@@ -0,0 +1,130 @@
+mod common;
+
+use common::{m4, m4_warnings, m4_with};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn comment_is_copied_through() {
+    assert_eq!(m4("text # a comment\nmore\n"), "text # a comment\nmore\n");
+}
+
+#[test]
+fn comment_directly_after_name() {
+    assert_eq!(m4("foo#bar\nbaz\n"), "foo#bar\nbaz\n");
+}
+
+#[test]
+fn name_before_comment_is_looked_up_alone() {
+    // `divert' expands to nothing, so only the comment survives
+    assert_eq!(m4("divert#c\nx"), "#c\nx");
+}
+
+#[test]
+fn comment_directly_after_quoted_string() {
+    assert_eq!(m4("define(`x', X)`name'#c x\nx"), "name#c x\nX");
+}
+
+#[test]
+fn comment_directly_after_call_with_arguments() {
+    assert_eq!(m4("define(`f', [$1])f(a)#c f(b)\nf(c)"), "[a]#c f(b)\n[c]");
+}
+
+#[test]
+fn text_after_comment_resumes_on_next_line() {
+    assert_eq!(m4("foo#c\ndivert"), "foo#c\n");
+}
+
+#[test]
+fn changecom_sets_multi_character_delimiters() {
+    assert_eq!(
+        m4("changecom(/*,*/)x /* divert\n */ divert # divert\n"),
+        "x /* divert\n */  # \n"
+    );
+}
+
+#[test]
+fn name_before_multi_character_comment_is_looked_up_alone() {
+    assert_eq!(m4("changecom(/*,*/)divert/*c*/x"), "/*c*/x");
+}
+
+#[test]
+fn comment_end_defaults_to_newline() {
+    assert_eq!(m4("changecom(%)% divert\ndivert"), "% divert\n");
+}
+
+#[test]
+fn changecom_without_arguments_disables_comments() {
+    assert_eq!(m4("changecom # divert\n"), " # \n");
+}
+
+#[test]
+fn changecom_with_empty_start_disables_comments() {
+    assert_eq!(m4("changecom()# divert\n"), "# \n");
+    assert_eq!(m4("changecom(,%)# divert % divert\n"), "#  % \n");
+}
+
+#[test]
+fn builder_comment_end_defaults_to_newline() {
+    assert_eq!(
+        m4_with(EngineBuilder::new().comments("%", ""), "% divert\ndivert"),
+        "% divert\n"
+    );
+}
+
+#[test]
+fn comments_go_to_the_current_diversion() {
+    assert_eq!(
+        m4("divert(1)# one\ndivert(-1)# gone\ndivert(0)zero "),
+        "zero # one\n"
+    );
+}
+
+#[test]
+fn comments_in_arguments_are_kept_whole() {
+    assert_eq!(m4("define(`x', y)len(# x, `x'\n)"), "9");
+}
+
+#[test]
+fn end_of_file_in_a_comment_is_an_error() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "a\nchangecom(/*,*/)/* b\nc");
+    assert_eq!(output, "a\n/* b\nc");
+    assert_eq!(warnings, "m4:stdin:2: end of file in comment\n");
+}
+
+#[test]
+fn comment_start_is_plain_text_in_quotes() {
+    assert_eq!(m4("define(`x', X)`a # x' x\n"), "a # x X\n");
+}
+
+#[test]
+fn quotes_are_plain_text_in_comments() {
+    assert_eq!(m4("define(`x', X)# `x\nx'\n"), "# `x\nX'\n");
+}
+
+#[test]
+fn multi_character_delimiters_only_count_outside_each_other() {
+    assert_eq!(
+        m4("define(`x', X)changequote(<<, >>)changecom(/*, */)<</* x>> /* <<x */ x"),
+        "/* x /* <<x */ X"
+    );
+}
+
+#[test]
+fn new_comments_apply_to_the_rest_of_the_expansion() {
+    assert_eq!(
+        m4("define(`x', X)define(`c', `changecom(<!,!>)<!x!> x')c<!x!>#x"),
+        "<!x!> X<!x!>#X"
+    );
+}
+
+#[test]
+fn non_ascii_comments() {
+    assert_eq!(m4("define(`x', X)changecom(§, ¶)x §x¶ x"), "X §x¶ X");
+    assert_eq!(
+        m4_with(
+            EngineBuilder::new().comments("§", ""),
+            "define(`x', X)§x\nx"
+        ),
+        "§x\nX"
+    );
+}
@@ -0,0 +1,93 @@
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use lc_m4::EngineBuilder;
+
+/// Runs `input` through a default engine and returns what it wrote.
+pub fn m4(input: &str) -> String {
+    m4_with(EngineBuilder::new(), input)
+}
+
+/// Runs `input` through an engine built from `builder`, capturing output.
+pub fn m4_with(builder: EngineBuilder<'_>, input: &str) -> String {
+    let mut out = Vec::new();
+    let mut engine = builder.output(&mut out).build().unwrap();
+    engine.process(&mut input.as_bytes()).unwrap();
+    engine.finish().unwrap();
+    drop(engine);
+    String::from_utf8(out).unwrap()
+}
+
+/// Like [`m4_with`], but also returns the warnings written.
+pub fn m4_warnings(builder: EngineBuilder<'_>, input: &str) -> (String, String) {
+    let mut warnings = Vec::new();
+    let output = m4_with(builder.diagnostic_output(&mut warnings), input);
+    (output, String::from_utf8(warnings).unwrap())
+}
+
+/// Like [`m4`], but first reloads the frozen file `fixture` from
+/// `tests/fixtures`.
+pub fn m4_frozen(fixture: &str, input: &str) -> String {
+    let path = format!("{}/tests/fixtures/{fixture}", env!("CARGO_MANIFEST_DIR"));
+    let mut out = Vec::new();
+    let mut engine = EngineBuilder::new().output(&mut out).build().unwrap();
+    engine
+        .reload_state(&mut std::fs::File::open(path).unwrap())
+        .unwrap();
+    engine.process(&mut input.as_bytes()).unwrap();
+    engine.finish().unwrap();
+    drop(engine);
+    String::from_utf8(out).unwrap()
+}
+
+/// Writes `contents` to a file in the temporary directory, unique to this
+/// test process.
+pub fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("lc-m4-test-{}-{name}", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// The lc-m4 binary, set up to run with `args`.
+pub fn m4_command(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_lc-m4"));
+    command.args(args);
+    command
+}
+
+/// Runs `command`, feeding it `stdin`, and returns what it wrote to stdout
+/// and stderr and its exit status. The input is written from another
+/// thread, so a run that stops reading early doesn't fail or block.
+pub fn run_command(command: &mut Command, stdin: &[u8]) -> (Vec<u8>, Vec<u8>, Option<i32>) {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut pipe = child.stdin.take().unwrap();
+    let stdin = stdin.to_vec();
+    let writer = thread::spawn(move || {
+        let _ = pipe.write_all(&stdin);
+    });
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap();
+    (output.stdout, output.stderr, output.status.code())
+}
+
+/// Runs the binary with `args`, feeding it `stdin`, and returns its output,
+/// diagnostics and exit status.
+pub fn m4_cli(args: &[&str], stdin: &str) -> (String, String, Option<i32>) {
+    let (stdout, stderr, status) = run_command(&mut m4_command(args), stdin.as_bytes());
+    (
+        String::from_utf8(stdout).unwrap(),
+        String::from_utf8(stderr).unwrap(),
+        status,
+    )
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use std::env;
+use std::fs;
+
+use common::{m4, m4_cli, m4_warnings, m4_with, temp_file};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn diversion_minus_one_discards() {
+    assert_eq!(m4("a\ndivert(-1)b\ndivert(0)c\n"), "a\nc\n");
+}
+
+#[test]
+fn divert_without_arguments_returns_to_output() {
+    assert_eq!(m4("divert(-1)gone\ndivert\nkept\n"), "\nkept\n");
+}
+
+#[test]
+fn empty_argument_is_zero_with_a_warning() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "divert(-1)a\ndivert()b\n");
+    assert_eq!(output, "b\n");
+    assert_eq!(
+        warnings,
+        "m4:stdin:2: Warning: empty string treated as 0 in builtin `divert'\n"
+    );
+}
+
+#[test]
+fn non_numeric_argument_is_ignored_with_a_warning() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "divert(-1)a\ndivert(x)b\n");
+    assert_eq!(output, "");
+    assert_eq!(
+        warnings,
+        "m4:stdin:2: Warning: non-numeric argument to builtin `divert'\n"
+    );
+}
+
+#[test]
+fn undivert_brings_back_named_diversions() {
+    assert_eq!(
+        m4("divert(1)one\ndivert(2)two\ndivert(0)undivert(2)undivert(1)"),
+        "two\none\n"
+    );
+}
+
+#[test]
+fn undivert_without_arguments_brings_back_all_in_order() {
+    assert_eq!(
+        m4("divert(2)two\ndivert(1)one\ndivert(0)undivert"),
+        "one\ntwo\n"
+    );
+}
+
+#[test]
+fn undivert_empties_the_diversion() {
+    assert_eq!(m4("divert(1)x\ndivert(0)undivert(1)undivert(1)"), "x\n");
+}
+
+#[test]
+fn undivert_into_another_diversion() {
+    assert_eq!(
+        m4("divert(1)x\ndivert(2)undivert(1)divert(0)undivert(2)"),
+        "x\n"
+    );
+}
+
+#[test]
+fn current_diversion_is_not_undiverted() {
+    assert_eq!(m4("divert(1)a\nundivert(1)divert(0)undivert"), "a\n");
+}
+
+#[test]
+fn undivert_all_skips_the_current_diversion() {
+    assert_eq!(
+        m4("divert(1)one divert(3)three divert(2)two undivert`'divert(0)undivert(1)"),
+        "two one three "
+    );
+}
+
+#[test]
+fn undiverting_into_a_discarding_diversion_loses_the_text() {
+    assert_eq!(
+        m4("divert(1)x\ndivert(-1)undivert(1)divert(0)undivert(1)"),
+        ""
+    );
+}
+
+#[test]
+fn undivert_of_a_file_copies_it_unscanned() {
+    let path = temp_file("undivert", "divert(1)text\n");
+    let input = format!("undivert(`{}')", path.display());
+    assert_eq!(m4(&input), "divert(1)text\n");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn undivert_of_a_file_searches_the_include_path() {
+    let dir = env::temp_dir().join(format!("lc-m4-test-{}-undiverts", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("undiverted.txt"), "len(x)").unwrap();
+    let builder = EngineBuilder::new().include_path(&dir);
+    assert_eq!(m4_with(builder, "undivert(undiverted.txt)"), "len(x)");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn undivert_of_a_missing_file_warns() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "undivert(/nonexistent/x)");
+    assert_eq!(output, "");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: cannot undivert `/nonexistent/x': No such file or directory\n"
+    );
+}
+
+#[test]
+fn traditional_undivert_rejects_file_names() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new().traditional(), "undivert(x)");
+    assert_eq!(output, "");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: non-numeric argument to builtin `undivert'\n"
+    );
+    assert_eq!(
+        m4_with(EngineBuilder::new().traditional(), "undivert(0)"),
+        ""
+    );
+}
+
+#[test]
+fn divnum_names_the_current_diversion() {
+    assert_eq!(m4("divnum divert(-1)divert(0)divnum"), "0 0");
+    assert_eq!(m4("divert(4)divnum divert(0)undivert(4)"), "4 ");
+}
+
+#[test]
+fn diversion_numbers_wrap_like_c_ints() {
+    assert_eq!(m4("divert(4294967300)divnum divert(0)undivert(4)"), "4 ");
+}
+
+#[test]
+fn negative_diversions_still_expand_macros() {
+    assert_eq!(
+        m4("divert(-1)define(x,y)text\ndivert(-5)more\ndivert x"),
+        " y"
+    );
+}
+
+#[test]
+fn discarded_text_is_reported_with_the_i_flag() {
+    let mut debug = Vec::new();
+    let builder = EngineBuilder::new()
+        .debug_flags("i")
+        .debug_output(&mut debug);
+    assert_eq!(m4_with(builder, "divert(-1)text\ndivert(0)divert(-2)x"), "");
+    assert_eq!(
+        String::from_utf8(debug).unwrap(),
+        "m4debug: 5 bytes discarded in diversion -1\nm4debug: 1 bytes discarded in diversion -2\n"
+    );
+    assert_eq!(m4("divert(-1)text\ndivert"), "");
+}
+
+#[test]
+fn remaining_diversions_are_written_to_stdout_in_order() {
+    let (output, _, _) = m4_cli(&[], "divert(10)ten\ndivert(2)two\ndivert(0)zero\n");
+    assert_eq!(output, "zero\ntwo\nten\n");
+}
+
+#[test]
+fn large_diversion_numbers_are_cheap_and_ordered() {
+    assert_eq!(
+        m4("divert(100000000)big divert(3)small divert(0)"),
+        "small big "
+    );
+    assert_eq!(
+        m4("divert(2000000000)big divert(3)small divert(0)undivert"),
+        "small big "
+    );
+}
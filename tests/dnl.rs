@@ -0,0 +1,24 @@
+mod common;
+
+use common::m4_warnings;
+use lc_m4::EngineBuilder;
+
+#[test]
+fn dnl_arguments_are_ignored_with_a_warning() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "a dnl(x) gone\nb");
+    assert_eq!(output, "a b");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: excess arguments to builtin `dnl' ignored\n"
+    );
+}
+
+#[test]
+fn dnl_warns_at_end_of_file() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "a dnl gone");
+    assert_eq!(output, "a ");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: dnl: end of file treated as newline\n"
+    );
+}
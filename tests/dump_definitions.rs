@@ -0,0 +1,175 @@
+mod common;
+
+use std::env;
+use std::fs::{self, File};
+
+use common::m4_cli;
+use lc_m4::{DumpFormat, EngineBuilder};
+use serde_json::Value;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/frozen.m4f");
+
+/// Parses a JSON dump, checking every entry has the documented fields,
+/// and returns the entries.
+fn parse_json(json: &str) -> Vec<Value> {
+    let Value::Array(entries) = serde_json::from_str(json).unwrap() else {
+        panic!("not an array: {json}");
+    };
+    for entry in &entries {
+        let Value::Object(fields) = entry else {
+            panic!("not an object: {entry}");
+        };
+        let mut keys: Vec<&str> = fields.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            ["body", "kind", "name", "pushdef_depth", "traced"],
+            "{entry}"
+        );
+        assert!(
+            entry["name"].is_string() || entry["name"].is_array(),
+            "{entry}"
+        );
+        assert!(
+            matches!(entry["kind"].as_str(), Some("text" | "builtin")),
+            "{entry}"
+        );
+        assert!(
+            entry["body"].is_string() || entry["body"].is_array(),
+            "{entry}"
+        );
+        assert!(entry["traced"].is_boolean(), "{entry}");
+        assert!(
+            entry["pushdef_depth"]
+                .as_u64()
+                .is_some_and(|depth| depth > 0),
+            "{entry}"
+        );
+    }
+    entries
+}
+
+fn dump(format: DumpFormat) -> String {
+    let mut sink = Vec::new();
+    let mut engine = EngineBuilder::new()
+        .trace("foo")
+        .output(&mut sink)
+        .build()
+        .unwrap();
+    engine
+        .reload_state(&mut File::open(FIXTURE).unwrap())
+        .unwrap();
+    let mut out = Vec::new();
+    engine.dump_definitions(&mut out, format).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn json_lists_every_definition() {
+    assert_eq!(
+        dump(DumpFormat::Json),
+        concat!(
+            "[\n",
+            r#"  {"name": "define", "kind": "builtin", "body": "define", "traced": false, "pushdef_depth": 1},"#,
+            "\n",
+            r#"  {"name": "foo", "kind": "text", "body": "hello", "traced": true, "pushdef_depth": 2},"#,
+            "\n",
+            r#"  {"name": "quip", "kind": "text", "body": "a \"b\"\\c\nd", "traced": false, "pushdef_depth": 1}"#,
+            "\n]\n",
+        )
+    );
+}
+
+#[test]
+fn json_parses_as_documented() {
+    let entries = parse_json(&dump(DumpFormat::Json));
+    let names: Vec<&str> = entries.iter().filter_map(|e| e["name"].as_str()).collect();
+    assert_eq!(names, ["define", "foo", "quip"]);
+    assert_eq!(entries[1]["traced"], true);
+    assert_eq!(entries[1]["pushdef_depth"], 2);
+    assert_eq!(entries[2]["body"], "a \"b\"\\c\nd");
+}
+
+#[test]
+fn text_matches_dumpdef() {
+    assert_eq!(
+        dump(DumpFormat::Text),
+        "define:\t<define>\nfoo:\t`hello'\nquip:\t`a \"b\"\\c\nd'\n"
+    );
+}
+
+#[test]
+fn json_escapes_non_utf8_as_bytes() {
+    let mut out = Vec::new();
+    lc_m4::write_json_text(&mut out, b"a\xffb").unwrap();
+    assert_eq!(out, b"[97,255,98]");
+}
+
+#[test]
+fn cli_writes_dump_at_exit() {
+    let path = env::temp_dir().join(format!("lc-m4-dump-{}.json", std::process::id()));
+    let (_, _, status) = m4_cli(
+        &[
+            &format!("--reload-state={FIXTURE}"),
+            &format!("--dump-definitions={}:json", path.display()),
+        ],
+        "",
+    );
+    assert_eq!(status, Some(0));
+    let json = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let entries = parse_json(&json);
+    assert_eq!(entries[0]["name"], "define");
+    assert_eq!(entries[0]["kind"], "builtin");
+}
+
+#[test]
+fn cli_rejects_unknown_dump_formats() {
+    let path = env::temp_dir().join(format!("lc-m4-dump-{}.xml", std::process::id()));
+    let (_, errors, status) = m4_cli(&[&format!("--dump-definitions={}:xml", path.display())], "");
+    assert_eq!(status, Some(1));
+    assert!(
+        errors.ends_with(": unknown dump format `xml'\n"),
+        "{errors}"
+    );
+    assert!(!path.exists());
+}
+
+#[test]
+fn cli_keeps_other_colons_in_the_file_name() {
+    let path = env::temp_dir().join(format!("lc-m4-dump-{}:a.txt", std::process::id()));
+    let (_, _, status) = m4_cli(
+        &[&format!("--dump-definitions={}", path.display())],
+        "define(`x', `y')",
+    );
+    assert_eq!(status, Some(0));
+    let text = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(text.contains("x:\t`y'\n"), "{text}");
+}
+
+/// Processes `input`, then dumps every definition.
+fn dump_input(input: &[u8], format: DumpFormat) -> Vec<u8> {
+    let mut engine = EngineBuilder::new().output(Vec::new()).build().unwrap();
+    engine.process(&mut &input[..]).unwrap();
+    let mut out = Vec::new();
+    engine.dump_definitions(&mut out, format).unwrap();
+    out
+}
+
+#[test]
+fn dumps_keep_the_bytes_of_definitions() {
+    let input = b"define(`e', `h\xc3\xa9')define(`f', `\xff')";
+    let text = dump_input(input, DumpFormat::Text);
+    let expected = b"\ne:\t`h\xc3\xa9'\n";
+    assert!(text.windows(expected.len()).any(|w| w == expected));
+    assert!(text.windows(6).any(|w| w == b"f:\t`\xff'"));
+    let json = String::from_utf8(dump_input(input, DumpFormat::Json)).unwrap();
+    let entries = parse_json(&json);
+    let body = |name: &str| {
+        let entry = entries.iter().find(|entry| entry["name"] == name).unwrap();
+        entry["body"].clone()
+    };
+    assert_eq!(body("e"), "hé");
+    assert_eq!(body("f"), serde_json::json!([255]));
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::m4_with;
+use lc_m4::EngineBuilder;
+
+/// Runs `input` and returns what was written to the debug output.
+fn dumped(builder: EngineBuilder<'_>, input: &str) -> String {
+    let mut debug = Vec::new();
+    m4_with(builder.debug_output(&mut debug), input);
+    String::from_utf8(debug).unwrap()
+}
+
+#[test]
+fn dumpdef_writes_named_definitions_sorted() {
+    assert_eq!(
+        dumped(
+            EngineBuilder::new(),
+            "define(`foo', bar)dumpdef(`foo', `define')"
+        ),
+        "define:\t<define>\nfoo:\t`bar'\n"
+    );
+}
+
+#[test]
+fn dumpdef_without_arguments_dumps_everything_sorted() {
+    let dump = dumped(EngineBuilder::new().traditional(), "define(foo,bar)dumpdef");
+    let names: Vec<&str> = dump.lines().map(|l| l.split(':').next().unwrap()).collect();
+    let mut sorted = names.clone();
+    sorted.sort_unstable();
+    assert_eq!(names, sorted);
+    assert!(dump.contains("\ndumpdef:\t<dumpdef>\n"));
+    assert!(dump.contains("\nfoo:\t`bar'\n"));
+    assert!(!dump.contains("format"));
+}
+
+#[test]
+fn dumpdef_quotes_only_with_the_q_flag() {
+    let dump = dumped(
+        EngineBuilder::new().debug_flags("a"),
+        "define(foo,bar)dumpdef",
+    );
+    assert!(dump.contains("\nfoo:\tbar\n"), "{dump}");
+}
+
+#[test]
+fn dumpdef_shows_the_visible_definition() {
+    assert_eq!(
+        dumped(
+            EngineBuilder::new(),
+            "define(`foo', one)pushdef(`foo', two)dumpdef(`foo')"
+        ),
+        "foo:\t`two'\n"
+    );
+}
+
+#[test]
+fn dumpdef_warns_about_undefined_names() {
+    let mut warnings = Vec::new();
+    let debug = dumped(
+        EngineBuilder::new().diagnostic_output(&mut warnings),
+        "dumpdef(nope)",
+    );
+    assert_eq!(debug, "");
+    assert_eq!(warnings, b"m4:stdin:1: Warning: undefined macro `nope'\n");
+}
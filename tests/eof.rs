@@ -0,0 +1,65 @@
+//! Each input file on the command line is scanned to its own end: strings,
+//! comments, argument lists and `dnl` don't carry over into the next file.
+
+mod common;
+
+use std::fs;
+
+use common::{m4_cli, m4_warnings, temp_file};
+use lc_m4::EngineBuilder;
+
+/// Runs the binary on a file holding each of `files` in turn, returning
+/// the exit status, output and diagnostics.
+fn m4_files(files: &[&str]) -> (Option<i32>, String, String) {
+    let paths: Vec<_> = files
+        .iter()
+        .enumerate()
+        .map(|(i, contents)| temp_file(&format!("eof-{i}"), contents))
+        .collect();
+    let args: Vec<&str> = paths.iter().map(|path| path.to_str().unwrap()).collect();
+    let (output, errors, status) = m4_cli(&args, "");
+    for path in paths {
+        fs::remove_file(path).unwrap();
+    }
+    (status, output, errors)
+}
+
+#[test]
+fn strings_end_with_their_file() {
+    let (status, output, diagnostics) = m4_files(&["`open\n", "define(`x', X)x'\n"]);
+    assert_eq!(status, Some(1));
+    assert_eq!(output, "open\nX'\n");
+    assert!(diagnostics.contains("eof-0:1: end of file in string"));
+}
+
+#[test]
+fn comments_end_with_their_file() {
+    let (status, output, diagnostics) =
+        m4_files(&["changecom(`/*', `*/')/* open\n", "define(`x', X)x */\n"]);
+    assert_eq!(status, Some(1));
+    assert_eq!(output, "/* open\nX */\n");
+    assert!(diagnostics.contains("eof-0:1: end of file in comment"));
+}
+
+#[test]
+fn dnl_ends_with_its_file() {
+    let (status, output, diagnostics) = m4_files(&["a dnl", "b\n"]);
+    assert_eq!(status, Some(0));
+    assert_eq!(output, "a b\n");
+    assert!(diagnostics.contains("eof-0:1: Warning: dnl: end of file treated as newline"));
+}
+
+#[test]
+fn argument_lists_end_with_their_file() {
+    let (status, output, diagnostics) = m4_files(&["define(`x',\nX", ")x\n"]);
+    assert_eq!(status, Some(1));
+    assert_eq!(output, ")X\n");
+    assert!(diagnostics.contains("eof-0:1: end of file in argument list"));
+}
+
+#[test]
+fn open_argument_list_is_reported_where_it_started() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "\nlen(`abc'");
+    assert_eq!(output, "\n3");
+    assert_eq!(warnings, "m4:stdin:2: end of file in argument list\n");
+}
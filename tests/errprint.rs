@@ -0,0 +1,56 @@
+mod common;
+
+use std::fs::{self, File};
+
+use common::{m4_command, m4_warnings, temp_file};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn errprint_writes_to_diagnostics_verbatim() {
+    let (output, errors) = m4_warnings(EngineBuilder::new(), "errprint(oops)ok");
+    assert_eq!(output, "ok");
+    assert_eq!(errors, "oops");
+}
+
+#[test]
+fn errprint_joins_arguments_with_spaces() {
+    let (_, errors) = m4_warnings(EngineBuilder::new(), "errprint(a,b,c\n)");
+    assert_eq!(errors, "a b c\n");
+}
+
+/// Runs the binary on `input` with stdout and stderr going to one file,
+/// returning what the file holds afterwards.
+fn m4_combined(name: &str, input: &str, args: &[&str]) -> String {
+    let source = temp_file(&format!("{name}.m4"), input);
+    let combined = temp_file(&format!("{name}.out"), "");
+    let file = File::create(&combined).unwrap();
+    m4_command(&[source.to_str().unwrap()])
+        .args(args)
+        .stdout(file.try_clone().unwrap())
+        .stderr(file)
+        .status()
+        .unwrap();
+    let text = fs::read_to_string(&combined).unwrap();
+    fs::remove_file(source).unwrap();
+    fs::remove_file(combined).unwrap();
+    text
+}
+
+#[test]
+fn diagnostics_follow_the_output_before_them() {
+    assert_eq!(
+        m4_combined("interleaved", "a errprint(`b ')c", &[]),
+        "a b c"
+    );
+    let text = m4_combined("warned", "before eval(`1+') after", &[]);
+    let prefix = format!("before {}:", env!("CARGO_BIN_EXE_lc-m4"));
+    assert!(text.starts_with(&prefix), "{text}");
+    assert!(text.ends_with(" after"), "{text}");
+}
+
+#[test]
+fn missing_files_are_reported_after_earlier_output() {
+    let text = m4_combined("missing", "first\nno newline", &["/nonexistent/lc-m4"]);
+    assert!(text.starts_with("first\nno newline"), "{text}");
+    assert!(text.contains("cannot open"), "{text}");
+}
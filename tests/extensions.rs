@@ -0,0 +1,77 @@
+//! lc-m4's own builtins, enabled with `--extensions`. Quoting is turned off
+//! so values come out bare.
+
+mod common;
+
+use std::env;
+use std::fs;
+
+use common::{m4_command, m4_warnings, m4_with, run_command};
+use lc_m4::EngineBuilder;
+
+/// Runs the binary with `args` and `LC_M4_TEST` set, feeding `stdin`.
+fn m4_cli(args: &[&str], stdin: &str) -> String {
+    let mut command = m4_command(args);
+    command
+        .env("LC_M4_TEST", "some value")
+        .env_remove("LC_M4_UNSET");
+    let (output, _, _) = run_command(&mut command, stdin.as_bytes());
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn getenv_reads_the_environment() {
+    assert_eq!(
+        m4_cli(
+            &["--extensions"],
+            "changequote(,)getenv(LC_M4_TEST) getenv(LC_M4_UNSET) end"
+        ),
+        "some value  end"
+    );
+}
+
+#[test]
+fn extensions_are_off_by_default() {
+    assert_eq!(m4_cli(&[], "getenv(LC_M4_TEST)"), "getenv(LC_M4_TEST)");
+}
+
+#[test]
+fn extensions_need_gnu_mode_and_not_safer_mode() {
+    let traditional = EngineBuilder::new().traditional().extensions(true);
+    assert_eq!(m4_with(traditional, "getenv"), "getenv");
+    let safer = EngineBuilder::new().extensions(true).safer(true);
+    assert_eq!(m4_with(safer, "getenv"), "getenv");
+    let enabled = EngineBuilder::new().extensions(true);
+    assert_eq!(m4_with(enabled, "changequote(,)getenv(LC_M4_UNSET)"), "");
+}
+
+#[cfg(unix)]
+#[test]
+fn mkdtemp_creates_a_private_directory() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let template = env::temp_dir().join("lc-m4-mkdtemp-XXX");
+    let template = template.to_str().unwrap();
+    let builder = EngineBuilder::new().extensions(true);
+    let name = m4_with(builder, &format!("changequote(,)mkdtemp({template})"));
+    let stem = template.trim_end_matches('X');
+    assert!(name.starts_with(stem), "{name}");
+    assert_eq!(name.len(), stem.len() + 6, "{name}");
+    let metadata = fs::metadata(&name).unwrap();
+    fs::remove_dir(&name).unwrap();
+    assert!(metadata.is_dir());
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o700);
+}
+
+#[test]
+fn mkdtemp_failure_is_an_error() {
+    let builder = EngineBuilder::new().extensions(true);
+    let (output, warnings) = m4_warnings(builder, "mkdtemp(/nonexistent/dirXXXXXX)");
+    assert_eq!(output, "");
+    assert!(
+        warnings.starts_with(
+            "m4:stdin:1: cannot create directory from template `/nonexistent/dirXXXXXX': "
+        ),
+        "{warnings}"
+    );
+}
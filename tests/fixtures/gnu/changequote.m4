@@ -0,0 +1,3 @@
+changequote(`[', `]')
+define([foo], [Macro [foo].])
+foo
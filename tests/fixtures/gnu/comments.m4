@@ -0,0 +1,2 @@
+`quoted text' # `commented text'
+`quoting inhibits' `#' `comments'
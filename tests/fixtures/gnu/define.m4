@@ -0,0 +1,2 @@
+define(`foo', `Hello world.')
+foo
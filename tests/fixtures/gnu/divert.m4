@@ -0,0 +1,6 @@
+divert(`1')
+Text diverted a first time.
+divert(`0')undivert(`1')dnl
+divert(`1')
+Text diverted a second time.
+divert(`0')undivert(`1')dnl
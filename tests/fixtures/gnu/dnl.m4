@@ -0,0 +1,2 @@
+define(`foo', `Macro `foo'.')dnl A very simple macro, indeed.
+foo
@@ -0,0 +1,2 @@
+define(`exch', `$2, $1')
+exch(`arg1', `arg2')
@@ -0,0 +1,4 @@
+define(`forloop', `pushdef(`$1', `$2')_forloop($@)popdef(`$1')')dnl
+define(`_forloop',
+  `$4`'ifelse($1, `$3', `', `define(`$1', incr($1))$0($@)')')dnl
+forloop(`i', `1', `8', `i ')
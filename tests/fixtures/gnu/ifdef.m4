@@ -0,0 +1,3 @@
+ifdef(`foo', ``foo' is defined', ``foo' is not defined')
+define(`foo', `')
+ifdef(`foo', ``foo' is defined', ``foo' is not defined')
@@ -0,0 +1,4 @@
+define(`cleanup', `This is the `cleanup' action.
+')
+m4wrap(`cleanup')
+This is the first and last normal input line.
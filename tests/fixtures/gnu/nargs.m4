@@ -0,0 +1,8 @@
+define(`nargs', `$#')dnl
+nargs
+nargs()
+nargs(`arg1', `arg2', `arg3')
+nargs(`commas can be quoted, like this')
+nargs(arg1#inside comments, commas do not separate arguments
+still arg1)
+nargs((unquoted parentheses, like this, group arguments))
@@ -0,0 +1,2 @@
+patsubst(`GNUs not Unix', `^', `OBS: ')
+regexp(`GNUs not Unix', `\w\(\w+\)$', `*** \& *** \1 ***')
@@ -0,0 +1,3 @@
+shift
+shift(`bar')
+shift(`foo', `bar', `baz')
@@ -0,0 +1,4 @@
+eval(`2 ** 10') len(`abc') substr(`gnus, gnats, and armadillos', `6', `5')
+translit(`GNUs not Unix', `A-Z', `a-z')
+index(`gnus, gnats, and armadillos', `nat')
+format(`Result is %d', eval(`2**15'))
@@ -0,0 +1,5 @@
+define(`f', `[$1][$2]')dnl
+f( a , 
+  b )
+f(
+)
@@ -0,0 +1,133 @@
+mod common;
+
+use common::{m4, m4_warnings, m4_with};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn plain_text_and_percent_signs() {
+    assert_eq!(m4("format(Result is %d%%,42)"), "Result is 42%");
+}
+
+#[test]
+fn integer_widths_and_flags() {
+    assert_eq!(
+        m4("format(%5d|%-5d|%05d|%+d|% d,42,42,42,5,5)"),
+        "   42|42   |00042|+5| 5"
+    );
+}
+
+#[test]
+fn integer_radixes() {
+    assert_eq!(
+        m4("changecom format(%x %X %o %#x %#o,255,255,8,255,8)"),
+        " ff FF 10 0xff 010"
+    );
+}
+
+#[test]
+fn integers_wrap_like_c() {
+    assert_eq!(m4("format(%u %hhd %ld,-1,255,-1)"), "4294967295 -1 -1");
+    assert_eq!(m4("format(%lu,-1)"), "18446744073709551615");
+    assert_eq!(m4("format(%d,4294967296)"), "0");
+}
+
+#[test]
+fn int_overflow_warns() {
+    let (output, warnings) = m4_warnings(
+        EngineBuilder::new(),
+        "format(%d|%x|%ld|%d,2147483648,4294967295,2147483648,-2147483648)",
+    );
+    assert_eq!(output, "-2147483648|ffffffff|2147483648|-2147483648");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: numeric overflow detected\n".repeat(2)
+    );
+}
+
+#[test]
+fn strings_and_characters() {
+    assert_eq!(
+        m4("format(%.3s|%5s|%-5s|%c%c,abcdef,ab,ab,72,105)"),
+        "abc|   ab|ab   |Hi"
+    );
+}
+
+#[test]
+fn star_takes_width_and_precision_from_arguments() {
+    assert_eq!(
+        m4("format(%*d|%-*d|%.*d|%*d,4,1,4,1,3,1,-3,1)"),
+        "   1|1   |001|1  "
+    );
+}
+
+#[test]
+fn floating_point_conversions() {
+    assert_eq!(
+        m4("format(%f %e %g %E,3.5,1234.5,0.0001,-2)"),
+        "3.500000 1.234500e+03 0.0001 -2.000000E+00"
+    );
+    assert_eq!(
+        m4("changecom format(%g %g %G %g %#g,100000,1000000,1e-5,0.5,0.5)"),
+        " 100000 1e+06 1E-05 0.5 0.500000"
+    );
+    assert_eq!(
+        m4("format(%08.2f|%-8.1f|%.0e,-3.25,2,150)"),
+        "-0003.25|2.0     |2e+02"
+    );
+}
+
+#[test]
+fn hexadecimal_floating_point() {
+    assert_eq!(
+        m4("format(%a %a %a %a %A,1,3,-2,0,0.5)"),
+        "0x1p+0 0x1.8p+1 -0x1p+1 0x0p+0 0X1P-1"
+    );
+    assert_eq!(
+        m4("changecom format(%.2a|%.0a|%.0a|%#.0a|%010a|%-8a|,1,1.5,2.5,1,1,1)"),
+        " 0x1.00p+0|0x2p+0|0x1p+1|0x1.p+0|0x00001p+0|0x1p+0  |"
+    );
+    assert_eq!(m4("format(%a,4.9e-324)"), "0x0.0000000000001p-1022");
+}
+
+#[test]
+fn bad_specifiers_and_numbers_warn() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "format(a%zb)");
+    assert_eq!(output, "ab");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: unrecognized specifier in `a%zb'\n"
+    );
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "format(%d,x)");
+    assert_eq!(output, "0");
+    assert_eq!(warnings, "m4:stdin:1: Warning: non-numeric argument x\n");
+}
+
+#[test]
+fn floating_point_arguments_read_the_longest_number() {
+    let (output, warnings) = m4_warnings(
+        EngineBuilder::new(),
+        "format(%g|%g|%g|%g|%G, 1.5e, 2.5x, -inf, .5e+1, nanny)",
+    );
+    assert_eq!(output, "1.5|2.5|-inf|5|NAN");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: non-numeric argument 1.5e\n\
+         m4:stdin:1: Warning: non-numeric argument 2.5x\n\
+         m4:stdin:1: Warning: non-numeric argument nanny\n"
+    );
+    let (digits, tail) = ("1".repeat(50_000), "x".repeat(50_000));
+    let input = format!("format(%g,{digits}{tail})");
+    let (output, _) = m4_warnings(EngineBuilder::new(), &input);
+    assert_eq!(output, "inf");
+}
+
+#[test]
+fn missing_arguments_are_zero_or_empty() {
+    assert_eq!(m4("format(%d|%s|%f)"), "0||0.000000");
+}
+
+#[test]
+fn format_needs_gnu_mode() {
+    let builder = EngineBuilder::new().traditional();
+    assert_eq!(m4_with(builder, "format"), "format");
+}
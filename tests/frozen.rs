@@ -0,0 +1,67 @@
+mod common;
+
+use std::fs;
+
+use common::{m4_cli, temp_file};
+use lc_m4::{EngineBuilder, Error};
+
+fn reload(frozen: &str) -> Result<(), Error> {
+    let mut engine = EngineBuilder::new().output(Vec::new()).build().unwrap();
+    engine.reload_state(&mut frozen.as_bytes())
+}
+
+#[test]
+fn well_formed_records_reload() {
+    assert!(reload("# comment\nV1\nT3,5\nfoohello\nQ1,1\n[]\n").is_ok());
+}
+
+#[test]
+fn malformed_records_are_errors() {
+    let cases = [
+        ("V2\n", "incorrect/missing version in V declaration"),
+        ("V1x", "missing newline after V declaration"),
+        ("T3,5\nfoohello!", "missing newline after T declaration"),
+        ("F6,6\ndefinedefine!", "missing newline after F declaration"),
+        ("Q1,1\n[]!", "missing newline after Q declaration"),
+    ];
+    for (frozen, reason) in cases {
+        let error = reload(frozen).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("syntax error in reload state file: {reason}"),
+            "{frozen:?}"
+        );
+    }
+}
+
+#[test]
+fn malformed_numbers_and_short_strings_are_errors() {
+    let cases = [
+        ("T1, 2\nab\n", "bad number"),
+        ("T99999999999999999999,1\nab\n", "bad number"),
+        ("T1,x\nab\n", "bad number"),
+        ("T3,5\nfoo", "premature end of file"),
+    ];
+    for (frozen, reason) in cases {
+        let error = reload(frozen).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("syntax error in reload state file: {reason}"),
+            "{frozen:?}"
+        );
+    }
+}
+
+#[test]
+fn cli_reports_a_malformed_frozen_file() {
+    let path = temp_file("bad.m4f", "V1\nT3,5\nfoohello!");
+    let (_, errors, status) = m4_cli(&[&format!("--reload-state={}", path.display())], "");
+    fs::remove_file(&path).unwrap();
+    assert_eq!(status, Some(1));
+    assert!(
+        errors.ends_with(
+            ": syntax error in reload state file: missing newline after T declaration\n"
+        ),
+        "{errors}"
+    );
+}
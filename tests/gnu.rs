@@ -0,0 +1,32 @@
+//! A corpus of inputs, mostly from the GNU m4 manual, each with the output
+//! GNU m4 1.4.19 writes for it, whitespace and all. Every `name.m4` in
+//! `tests/fixtures/gnu` is run through the binary and its stdout compared
+//! byte for byte with `name.out`.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{m4_command, run_command};
+
+#[test]
+fn output_matches_gnu_m4() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/gnu");
+    let mut inputs: Vec<_> = fs::read_dir(&corpus)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "m4"))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty());
+    let mut mismatched = Vec::new();
+    for input in inputs {
+        let expected = fs::read(input.with_extension("out")).unwrap();
+        let (output, _, _) = run_command(&mut m4_command(&[input.to_str().unwrap()]), b"");
+        if output != expected {
+            mismatched.push(input.file_name().unwrap().to_string_lossy().into_owned());
+        }
+    }
+    assert!(mismatched.is_empty(), "output differs for {mismatched:?}");
+}
@@ -0,0 +1,24 @@
+mod common;
+
+use common::m4;
+
+#[test]
+fn ifelse_chooses_by_comparison() {
+    assert_eq!(m4("ifelse(a,a,yes,no) ifelse(a,b,yes,no)"), "yes no");
+    assert_eq!(m4("ifelse(a,b,yes)"), "");
+}
+
+#[test]
+fn ifelse_chains_comparisons() {
+    assert_eq!(m4("ifelse(a,b,1,c,c,2,3)"), "2");
+    assert_eq!(m4("ifelse(a,b,1,c,d,2,3)"), "3");
+    assert_eq!(m4("ifelse(a,b,1,c,d,2)"), "");
+}
+
+// From GNU m4: with five arguments left, the fourth is the default
+#[test]
+fn ifelse_with_five_arguments_uses_the_fourth_as_default() {
+    assert_eq!(m4("ifelse(a,b,c,d,e)"), "d");
+    assert_eq!(m4("ifelse(a,a,c,d,e)"), "c");
+    assert_eq!(m4("ifelse(x,y,1,a,b,c,d,e)"), "d");
+}
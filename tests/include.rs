@@ -0,0 +1,114 @@
+mod common;
+
+use std::env;
+use std::fs;
+
+use common::{m4, m4_cli, m4_warnings, m4_with, temp_file};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn included_file_is_scanned_in_place() {
+    let path = temp_file("include", "divnum\n");
+    let input = format!("a include({}) b", path.display());
+    assert_eq!(m4(&input), "a 0\n b");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn relative_names_are_searched_along_the_include_path() {
+    let dir = env::temp_dir().join(format!("lc-m4-test-{}-includes", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("included.m4"), "found").unwrap();
+    let builder = EngineBuilder::new().include_path(&dir);
+    assert_eq!(m4_with(builder, "include(included.m4)"), "found");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn missing_include_is_an_error() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "include(/nonexistent/x)after");
+    assert_eq!(output, "after");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: cannot open file `/nonexistent/x': No such file or directory\n"
+    );
+}
+
+#[test]
+fn missing_sinclude_is_silent() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "sinclude(/nonexistent/x)after");
+    assert_eq!(output, "after");
+    assert_eq!(warnings, "");
+}
+
+#[test]
+fn missing_include_fails_the_run() {
+    let (output, _, status) = m4_cli(&[], "include(/nonexistent/x)after\n");
+    assert_eq!((status, output.as_str()), (Some(1), "after\n"));
+}
+
+#[test]
+fn recursive_include_stops_with_an_error() {
+    let path = temp_file("recursive", "");
+    fs::write(&path, format!("x include({})", path.display())).unwrap();
+    let (output, warnings) = m4_warnings(
+        EngineBuilder::new(),
+        &format!("include({})", path.display()),
+    );
+    fs::remove_file(&path).unwrap();
+    // stdin is the first of the files being read
+    assert_eq!(output, "x ".repeat(255));
+    assert_eq!(
+        warnings,
+        format!(
+            "m4:{0}:1: recursive include of file `{0}'\n",
+            path.display()
+        )
+    );
+}
+
+#[test]
+fn earlier_include_directories_win() {
+    let base = env::temp_dir().join(format!("lc-m4-test-{}-order", std::process::id()));
+    let (first, second) = (base.join("first"), base.join("second"));
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+    fs::write(first.join("both.m4"), "first").unwrap();
+    fs::write(second.join("both.m4"), "second").unwrap();
+    fs::write(second.join("only.m4"), "only").unwrap();
+    let builder = EngineBuilder::new()
+        .include_path(&first)
+        .include_path(&second);
+    let output = m4_with(builder, "include(both.m4) sinclude(only.m4)");
+    fs::remove_dir_all(base).unwrap();
+    assert_eq!(output, "first only");
+}
+
+#[test]
+fn command_line_files_are_searched_along_the_include_path() {
+    let dir = env::temp_dir().join(format!("lc-m4-test-{}-cli-includes", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("named.m4"), "named __file__\n").unwrap();
+    let (output, _, status) = m4_cli(&["-I", dir.to_str().unwrap(), "named.m4"], "");
+    fs::remove_dir_all(dir).unwrap();
+    assert_eq!((status, output.as_str()), (Some(0), "named named.m4\n"));
+}
+
+#[test]
+fn dnl_stops_at_the_end_of_an_included_file() {
+    let path = temp_file("include-ending", "a dnl");
+    let (output, warnings) = m4_warnings(
+        EngineBuilder::new(),
+        &format!("include(`{}')\nafter", path.display()),
+    );
+    fs::remove_file(&path).unwrap();
+    // The newline after the include is left alone
+    assert_eq!(output, "a \nafter");
+    assert_eq!(
+        warnings,
+        format!(
+            "m4:{}:1: Warning: dnl: end of file treated as newline\n",
+            path.display()
+        )
+    );
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use common::{m4, m4_warnings, m4_with};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn indir_calls_by_name() {
+    assert_eq!(m4("indir(`len', abc) indir(`divnum')"), "3 0");
+    assert_eq!(m4("define(`x', `[$1]')indir(`x', y)"), "[y]");
+}
+
+#[test]
+fn indir_reaches_names_that_are_not_words() {
+    assert_eq!(m4("define(`a-b', ok)a-b indir(`a-b')"), "a-b ok");
+}
+
+#[test]
+fn indir_warns_about_undefined_names() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "indir(nope,x)");
+    assert_eq!(output, "");
+    assert_eq!(warnings, "m4:stdin:1: Warning: undefined macro `nope'\n");
+}
+
+#[test]
+fn indir_is_gnu_only() {
+    assert_eq!(
+        m4_with(EngineBuilder::new().traditional(), "indir(x)"),
+        "indir(x)"
+    );
+}
@@ -0,0 +1,69 @@
+//! `__file__`, `__line__` and `__program__`. Quoting is turned off so the
+//! file and program names come out bare.
+
+mod common;
+
+use std::fs;
+
+use common::{m4, m4_with, temp_file};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn lines_of_stdin_are_counted() {
+    assert_eq!(
+        m4("changequote(,)__file__ __line__\n__line__\n\n__line__"),
+        "stdin 1\n2\n\n4"
+    );
+}
+
+#[test]
+fn expansions_do_not_advance_the_line() {
+    assert_eq!(m4("define(nl,`\n')nl nl __line__"), "\n \n 2");
+    assert_eq!(m4("len(`\n\n') __line__"), "2 3");
+}
+
+#[test]
+fn included_files_have_their_own_lines() {
+    let path = temp_file("location.m4", "__file__ __line__\n\n__line__\n");
+    let name = path.to_str().unwrap();
+    let out = m4(&format!("changequote(,)\ninclude({name})__file__ __line__"));
+    fs::remove_file(&path).unwrap();
+    assert_eq!(out, format!("\n{name} 1\n\n3\nstdin 2"));
+}
+
+#[test]
+fn named_input_is_reported_by_name() {
+    let mut out = Vec::new();
+    let mut engine = EngineBuilder::new().output(&mut out).build().unwrap();
+    engine
+        .process_named("first.m4", &mut "changequote(,)__file__\n".as_bytes())
+        .unwrap();
+    engine
+        .process_named("second.m4", &mut "\n__file__ __line__".as_bytes())
+        .unwrap();
+    engine.finish().unwrap();
+    drop(engine);
+    assert_eq!(out, b"first.m4\n\nsecond.m4 2");
+}
+
+#[test]
+fn program_name_is_reported() {
+    assert_eq!(
+        m4_with(
+            EngineBuilder::new().program_name("gm4"),
+            "changequote(,)__program__"
+        ),
+        "gm4"
+    );
+}
+
+#[test]
+fn location_builtins_are_gnu_only() {
+    assert_eq!(
+        m4_with(
+            EngineBuilder::new().traditional(),
+            "__file__ __line__ __program__"
+        ),
+        "__file__ __line__ __program__"
+    );
+}
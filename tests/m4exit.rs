@@ -0,0 +1,77 @@
+mod common;
+
+use common::{m4, m4_cli, m4_warnings};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn m4exit_stops_processing() {
+    assert_eq!(m4("before m4exit after"), "before ");
+    assert_eq!(m4("len(m4exit(2)) after"), "");
+}
+
+#[test]
+fn m4exit_inside_an_argument_list_leaves_it_unreported() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "a define(`x', m4exit(2))rest");
+    assert_eq!((output.as_str(), warnings.as_str()), ("a ", ""));
+    let (output, errors, status) = m4_cli(&[], "define(`x', m4exit(2))rest");
+    assert_eq!(
+        (status, output.as_str(), errors.as_str()),
+        (Some(2), "", "")
+    );
+}
+
+#[test]
+fn m4exit_sets_the_exit_status() {
+    let (output, _, status) = m4_cli(&[], "a\nm4exit\nb\n");
+    assert_eq!((status, output.as_str()), (Some(0), "a\n"));
+    let (output, _, status) = m4_cli(&[], "a\nm4exit(3)\nb\n");
+    assert_eq!((status, output.as_str()), (Some(3), "a\n"));
+}
+
+#[test]
+fn m4exit_skips_later_files() {
+    let (output, _, status) = m4_cli(&["-", "-"], "m4exit(4)\n");
+    assert_eq!((status, output.as_str()), (Some(4), ""));
+}
+
+#[test]
+fn m4exit_zero_keeps_an_earlier_failure() {
+    let (_, _, status) = m4_cli(&[], "include(/nonexistent/x)m4exit(0)");
+    assert_eq!(status, Some(1));
+}
+
+#[test]
+fn m4exit_discards_wrapped_text_but_flushes_diversions() {
+    assert_eq!(
+        m4("m4wrap(wrapped)divert(1)diverted divert(0)m4exit"),
+        "diverted "
+    );
+    assert_eq!(m4("divert(2)two divert(1)one m4exit ignored"), "one two ");
+}
+
+#[test]
+fn diversions_are_flushed_after_wrapped_text() {
+    assert_eq!(
+        m4("m4wrap(wrapped)divert(1)early divert(0)main "),
+        "main wrappedearly "
+    );
+}
+
+#[test]
+fn m4exit_rejects_bad_statuses() {
+    let (_, warnings) = m4_warnings(EngineBuilder::new(), "m4exit(256)");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: exit status out of range: `256'\n"
+    );
+    for input in ["m4exit(-1)", "m4exit(x)"] {
+        let (output, _, status) = m4_cli(&[], input);
+        assert_eq!((status, output.as_str()), (Some(1), ""), "{input}");
+    }
+}
+
+#[test]
+fn m4exit_status_wraps_like_a_c_int() {
+    let (output, _, status) = m4_cli(&[], "m4exit(4294967298)");
+    assert_eq!((status, output.as_str()), (Some(2), ""));
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::m4_warnings;
+use lc_m4::{BuildError, EngineBuilder};
+
+fn default_sequence() -> EngineBuilder<'static> {
+    EngineBuilder::new().warn_macro_sequence(EngineBuilder::DEFAULT_MACRO_SEQUENCE)
+}
+
+#[test]
+fn multi_digit_and_braced_references_are_flagged() {
+    let (_, warnings) = m4_warnings(default_sequence(), "define(x,$1 $10 ${2})");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: definition of `x' contains sequence `$10'\n\
+         m4:stdin:1: Warning: definition of `x' contains sequence `${2}'\n"
+    );
+}
+
+#[test]
+fn pushdef_is_checked_too() {
+    let (_, warnings) = m4_warnings(default_sequence(), "pushdef(y,$12)");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: definition of `y' contains sequence `$12'\n"
+    );
+}
+
+#[test]
+fn custom_sequences_and_no_warning_by_default() {
+    let builder = EngineBuilder::new().warn_macro_sequence(r"a\|b");
+    let (_, warnings) = m4_warnings(builder, "define(x,cab)");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: definition of `x' contains sequence `a'\n\
+         m4:stdin:1: Warning: definition of `x' contains sequence `b'\n"
+    );
+    let (_, warnings) = m4_warnings(EngineBuilder::new(), "define(x,$10)");
+    assert_eq!(warnings, "");
+}
+
+#[test]
+fn empty_matches_are_skipped() {
+    let builder = EngineBuilder::new().warn_macro_sequence("z*");
+    let (_, warnings) = m4_warnings(builder, "define(x,azzb)");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: definition of `x' contains sequence `zz'\n"
+    );
+}
+
+#[test]
+fn malformed_sequence_fails_the_build() {
+    assert!(matches!(
+        EngineBuilder::new().warn_macro_sequence(r"\(").build(),
+        Err(BuildError::BadMacroSequence { .. })
+    ));
+}
@@ -0,0 +1,21 @@
+//! Macro names are words of letters, digits and underscores, not starting
+//! with a digit; everything else passes through.
+
+mod common;
+
+use common::m4;
+
+#[test]
+fn names_end_at_punctuation() {
+    assert_eq!(m4("define(`foo', X)foo(bar)baz foo.foo-foo"), "Xbaz X.X-X");
+}
+
+#[test]
+fn names_take_digits_and_underscores() {
+    assert_eq!(m4("define(`a_1', y)a_1 1a_1 a_10 _a_1"), "y 1y a_10 _a_1");
+}
+
+#[test]
+fn other_bytes_pass_through() {
+    assert_eq!(m4("define(`x', y)@x%x$x"), "@y%y$y");
+}
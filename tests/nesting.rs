@@ -0,0 +1,58 @@
+mod common;
+
+use common::m4_cli;
+use lc_m4::{EngineBuilder, Error};
+
+/// Runs `input` with at most `limit` nested calls.
+fn run(limit: u64, input: &str) -> Result<String, Error> {
+    let mut out = Vec::new();
+    let mut engine = EngineBuilder::new()
+        .nesting_limit(limit)
+        .output(&mut out)
+        .build()
+        .unwrap();
+    engine.process(&mut input.as_bytes())?;
+    engine.finish()?;
+    drop(engine);
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn calls_may_nest_up_to_the_limit() {
+    assert_eq!(run(3, "len(len(len(abc)))").unwrap(), "1");
+    assert!(matches!(
+        run(3, "len(len(len(len(abc))))"),
+        Err(Error::NestingLimit(3))
+    ));
+}
+
+#[test]
+fn zero_means_unlimited() {
+    let input = format!("{}x{}", "len(".repeat(200), ")".repeat(200));
+    assert_eq!(run(0, &input).unwrap(), "1");
+}
+
+#[test]
+fn runaway_recursion_is_stopped() {
+    assert!(matches!(
+        run(100, "define(`x', `x(x')x"),
+        Err(Error::NestingLimit(100))
+    ));
+}
+
+#[test]
+fn command_line_reports_the_limit() {
+    let (_, errors, status) = m4_cli(&["--nesting-limit=10"], "define(`x', `x(x')x");
+    assert_eq!(status, Some(1));
+    assert!(
+        errors.ends_with(": recursion limit of 10 exceeded, use -L<N> to change it\n"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn deep_nesting_does_not_overflow_the_stack() {
+    let depth = 100_000;
+    let input = format!("{}x{}", "len(".repeat(depth), ")".repeat(depth));
+    assert_eq!(run(0, &input).unwrap(), "1");
+}
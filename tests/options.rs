@@ -0,0 +1,109 @@
+//! Parsing the command line: long options with `=` or a separate value,
+//! and short options, bundled or with attached values.
+
+mod common;
+
+use std::fs;
+
+use common::{m4_cli, temp_file};
+
+fn stdout_of(args: &[&str], stdin: &str) -> String {
+    let (output, errors, status) = m4_cli(args, stdin);
+    assert_eq!(status, Some(0), "{args:?}: {errors}");
+    output
+}
+
+#[test]
+fn values_can_be_attached_or_separate() {
+    for args in [
+        &["-U", "len"][..],
+        &["-Ulen"],
+        &["--undefine=len"],
+        &["--undefine", "len"],
+    ] {
+        assert_eq!(stdout_of(args, "len(ab)"), "len(ab)", "{args:?}");
+    }
+}
+
+#[test]
+fn short_flags_can_be_bundled() {
+    assert_eq!(
+        stdout_of(&["-Gs"], "__gnu__\n"),
+        "#line 1 \"stdin\"\n__gnu__\n"
+    );
+    assert_eq!(stdout_of(&["-sUlen"], "len\n"), "#line 1 \"stdin\"\nlen\n");
+}
+
+#[test]
+fn short_equivalents_of_long_options() {
+    let (_, trace, _) = m4_cli(&["-t", "len"], "len(ab)");
+    assert!(trace.contains("m4trace: -1- len"), "{trace}");
+    let (_, _, status) = m4_cli(&["-L3"], "define(`x', `x(x')x");
+    assert_eq!(status, Some(1));
+    assert_eq!(stdout_of(&["-g"], "__gnu__"), "");
+    assert_eq!(stdout_of(&["-W", "[a-z]+"], "divert_x"), "_x");
+    assert_eq!(stdout_of(&["-P"], "m4_len(ab) len"), "2 len");
+}
+
+#[test]
+fn include_directories() {
+    let path = temp_file("options-include", "included");
+    let dir = path.parent().unwrap().to_str().unwrap();
+    let name = path.file_name().unwrap().to_str().unwrap();
+    let input = format!("include(`{name}')");
+    let attached = stdout_of(&[&format!("-I{dir}")], &input);
+    let separate = stdout_of(&["-I", dir], &input);
+    let long = stdout_of(&["--include", dir], &input);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        (attached.as_str(), separate.as_str()),
+        ("included", "included")
+    );
+    assert_eq!(long, "included");
+}
+
+#[test]
+fn double_dash_ends_the_options() {
+    let (output, errors, status) = m4_cli(&["--", "-s", "-"], "text");
+    assert_eq!((status, output.as_str()), (Some(1), "text"));
+    assert!(errors.contains("cannot open `-s'"), "{errors}");
+}
+
+#[test]
+fn bad_options_are_rejected() {
+    for args in [&["-k"][..], &["--no-such-option"], &["-I"], &["--gnu=yes"]] {
+        let (_, _, status) = m4_cli(args, "");
+        assert_eq!(status, Some(1), "{args:?}");
+    }
+}
+
+#[test]
+fn definitions_apply_in_order_before_any_input() {
+    assert_eq!(stdout_of(&["-DX=1", "-UX"], "X"), "X");
+    assert_eq!(stdout_of(&["-UX", "-DX=1"], "X"), "1");
+    assert_eq!(stdout_of(&["-DX=1", "--define=X=2"], "X"), "2");
+    assert_eq!(stdout_of(&["--define", "X=a=b"], "X"), "a=b");
+    assert_eq!(stdout_of(&["-DX"], "[X]"), "[]");
+    assert_eq!(stdout_of(&["-", "-DX=late"], "X"), "late");
+}
+
+#[test]
+fn definitions_keep_the_bytes_of_their_names() {
+    assert_eq!(stdout_of(&["-Dfé=x"], "ifdef(`fé', yes, no)"), "yes");
+    assert_eq!(stdout_of(&["-Dfé=x", "-Ufé"], "ifdef(`fé', yes, no)"), "no");
+}
+
+#[cfg(unix)]
+#[test]
+fn arguments_need_not_be_utf8() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    use common::{m4_command, run_command};
+
+    let mut command = m4_command(&[]);
+    command.arg(OsStr::from_bytes(b"-D\xff=\xfe"));
+    let (output, _, status) = run_command(&mut command, b"ifdef(`\xff', `defn(`\xff')')");
+    assert_eq!(status, Some(0));
+    assert_eq!(output, b"\xfe");
+}
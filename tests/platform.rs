@@ -0,0 +1,43 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{m4, m4_with};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn gnu_mode_defines_gnu_and_unix() {
+    assert_eq!(
+        m4("[ __gnu__ ] [ __unix__ ] [ unix ] [ __windows__ ] [ __os2__ ]"),
+        "[  ] [  ] [ unix ] [ __windows__ ] [ __os2__ ]"
+    );
+}
+
+#[test]
+fn traditional_mode_defines_plain_unix() {
+    assert_eq!(
+        m4_with(
+            EngineBuilder::new().traditional(),
+            "[ __gnu__ ] [ __unix__ ] [ unix ] [ windows ]"
+        ),
+        "[ __gnu__ ] [ __unix__ ] [  ] [ windows ]"
+    );
+}
+
+#[test]
+fn gnu_mode_defines_the_version() {
+    let version = lc_m4::VERSION;
+    assert_eq!(m4("__lc_m4_version__"), version);
+    assert_eq!(m4("__lc_m4__"), format!("lc-m4 {version}"));
+}
+
+#[test]
+fn traditional_mode_leaves_the_version_out() {
+    assert_eq!(
+        m4_with(
+            EngineBuilder::new().traditional(),
+            "__lc_m4_version__ __lc_m4__"
+        ),
+        "__lc_m4_version__ __lc_m4__"
+    );
+}
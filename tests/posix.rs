@@ -0,0 +1,303 @@
+//! Normative examples and requirements from the POSIX.1-2017 description of
+//! m4 (XCU, "m4 - macro processor"), run under `--traditional`. Each test
+//! names the part of the specification it transcribes.
+
+mod common;
+
+use std::fs;
+
+use common::{m4_cli, m4_with, temp_file};
+use lc_m4::EngineBuilder;
+
+fn posix(input: &str) -> String {
+    m4_with(EngineBuilder::new().traditional(), input)
+}
+
+/// Runs the binary in traditional mode with `args`, feeding `stdin`.
+fn posix_cli(args: &[&str], stdin: &str) -> (Option<i32>, String) {
+    let args: Vec<&str> = ["--traditional"].iter().chain(args).copied().collect();
+    let (output, _, status) = m4_cli(&args, stdin);
+    (status, output)
+}
+
+// OPERANDS: "If no file operands are given, or if a file operand is '-', the
+// standard input shall be read."
+#[test]
+fn operands_default_to_stdin() {
+    assert_eq!(
+        posix_cli(&[], "plain text\n"),
+        (Some(0), "plain text\n".into())
+    );
+}
+
+// OPERANDS: files are processed in the order given, '-' naming stdin.
+#[test]
+fn operands_are_read_in_order() {
+    let first = temp_file("first", "one\n");
+    let last = temp_file("last", "three\n");
+    let result = posix_cli(
+        &[first.to_str().unwrap(), "-", last.to_str().unwrap()],
+        "two\n",
+    );
+    fs::remove_file(first).unwrap();
+    fs::remove_file(last).unwrap();
+    assert_eq!(result, (Some(0), "one\ntwo\nthree\n".into()));
+}
+
+// EXIT STATUS: ">0 An error occurred." A file that can't be opened is an
+// error, but the remaining operands are still processed.
+#[test]
+fn unreadable_operand_fails_but_continues() {
+    let (code, out) = posix_cli(&["/nonexistent/lc-m4-input", "-"], "still read\n");
+    assert_eq!(code, Some(1));
+    assert_eq!(out, "still read\n");
+}
+
+// EXTENDED DESCRIPTION: "The comment ... shall be written to the output."
+#[test]
+fn comments_are_copied() {
+    assert_eq!(posix("a # comment `x'\nb\n"), "a # comment `x'\nb\n");
+}
+
+// EXTENDED DESCRIPTION: quoting strips one level of quotes and prevents
+// macro recognition.
+#[test]
+fn quotes_strip_one_level() {
+    assert_eq!(posix("`divert' ``nested''\n"), "divert `nested'\n");
+}
+
+// EXTENDED DESCRIPTION: "Macro calls have the form name(arg1, arg2, ...,
+// argn) ... Unquoted leading <blank> and <newline> characters are stripped
+// from arguments."
+#[test]
+fn leading_blanks_stripped_from_arguments() {
+    assert_eq!(posix("define(`f', `[$1]')f(  a  )\n"), "[a  ]\n");
+}
+
+// EXTENDED DESCRIPTION: "If the macro name is not followed by a <left-
+// parenthesis>, it is processed with no arguments."
+#[test]
+fn name_without_parenthesis_takes_no_arguments() {
+    assert_eq!(posix("define(`f', `[$#]')f (x)\n"), "[0] (x)\n");
+}
+
+// EXTENDED DESCRIPTION: "$#" ... "$*" ... "$@"
+#[test]
+fn argument_references() {
+    assert_eq!(
+        posix("define(`f', `$# $1 $*;$@')f(a, `b,c')\n"),
+        "2 a a,b,c;a,b,c\n"
+    );
+    assert_eq!(posix("define(`q', `$@')define(`b', `X')q(`b')\n"), "b\n");
+}
+
+// Macros: define, undefine, defn.
+#[test]
+fn define_undefine_defn() {
+    assert_eq!(posix("define(`x', `one')x undefine(`x')x\n"), "one x\n");
+    assert_eq!(
+        posix("define(`x', `$1!')define(`y', defn(`x'))y(hi)\n"),
+        "hi!\n"
+    );
+}
+
+// Macros: pushdef and popdef.
+#[test]
+fn pushdef_popdef() {
+    assert_eq!(
+        posix("define(`x', 1)pushdef(`x', 2)x popdef(`x')x popdef(`x')x\n"),
+        "2 1 x\n"
+    );
+}
+
+// Macros: dnl "shall cause m4 to discard all characters up to and including
+// the next <newline>."
+#[test]
+fn dnl_discards_through_newline() {
+    assert_eq!(posix("a dnl ignored `text'\nb\n"), "a b\n");
+}
+
+// Macros: ifdef.
+#[test]
+fn ifdef_checks_definitions() {
+    assert_eq!(
+        posix("ifdef(`define', yes, no) ifdef(`nope', yes, no)\n"),
+        "yes no\n"
+    );
+}
+
+// Macros: ifelse, including "If there are more than four arguments ... the
+// process shall be repeated with arguments 4, 5, 6, and 7".
+#[test]
+fn ifelse_chains() {
+    assert_eq!(posix("ifelse(a, a, yes, no)\n"), "yes\n");
+    assert_eq!(posix("ifelse(a, b, yes, no)\n"), "no\n");
+    assert_eq!(posix("ifelse(a, b, yes)\n"), "\n");
+    assert_eq!(posix("ifelse(a, b, 1, c, c, 2, 3)\n"), "2\n");
+    assert_eq!(posix("ifelse(a, b, 1, c, d, 2, 3)\n"), "3\n");
+}
+
+// Macros: shift "shall expand to all of its arguments except the first",
+// each quoted.
+#[test]
+fn shift_quotes_remaining_arguments() {
+    assert_eq!(posix("define(`b', `X')shift(a, `b', c)\n"), "b,c\n");
+}
+
+// Macros: changequote, and "If no arguments are given, the quotes shall be
+// reset to their default values".
+#[test]
+fn changequote_and_reset() {
+    assert_eq!(
+        posix("changequote([, ])[divert]changequote`divert'\n"),
+        "divertdivert\n"
+    );
+}
+
+// Macros: changecom.
+#[test]
+fn changecom_changes_comment_delimiters() {
+    assert_eq!(
+        posix("define(`x', X)changecom(`@@')x @@ x\nx\n"),
+        "X @@ x\nX\n"
+    );
+}
+
+// Macros: divert "shall cause m4 to output to diversion n", with diversions
+// 1 to 9 written at the end of input in order, and -1 discarded.
+#[test]
+fn diversions_are_flushed_in_order() {
+    assert_eq!(
+        posix("divert(2)two\ndivert(1)one\ndivert(-1)gone\ndivert(0)zero\n"),
+        "zero\none\ntwo\n"
+    );
+}
+
+// Macros: undivert and divnum.
+#[test]
+fn undivert_and_divnum() {
+    assert_eq!(posix("divert(1)x\ndivert(0)undivert(1)divnum\n"), "x\n0\n");
+    assert_eq!(posix("divert(3)divnum\n"), "3\n");
+}
+
+// Macros: translit, "If the third argument is omitted or shorter than the
+// second, characters ... shall be deleted".
+#[test]
+fn translit_maps_and_deletes() {
+    assert_eq!(posix("translit(`abcd', `ab', `AB')\n"), "ABcd\n");
+    assert_eq!(posix("translit(`abcd', `bd')\n"), "ac\n");
+}
+
+// Macros: index, len, substr.
+#[test]
+fn string_builtins() {
+    assert_eq!(posix("index(`hello', `l') index(`hello', `z')\n"), "2 -1\n");
+    assert_eq!(posix("len(`hello') len(`')\n"), "5 0\n");
+    assert_eq!(
+        posix("substr(`hello', 1, 3) substr(`hello', 2)\n"),
+        "ell llo\n"
+    );
+}
+
+// Macros: incr and decr.
+#[test]
+fn incr_decr() {
+    assert_eq!(posix("incr(4) decr(4) incr(-1)\n"), "5 3 0\n");
+}
+
+// Macros: eval, with the operators in order of decreasing precedence:
+// unary + - ~ !, binary * / %, + -, << >>, < <= > >=, == !=, &, ^, |, &&, ||.
+#[test]
+fn eval_operator_table() {
+    let cases = [
+        ("1+2*3", "7"),
+        ("(1+2)*3", "9"),
+        ("-7/2", "-3"),
+        ("-7%3", "-1"),
+        ("~0", "-1"),
+        ("!5", "0"),
+        ("+3-1", "2"),
+        ("1<<4>>2", "4"),
+        ("1<2==2>1", "1"),
+        ("3<=3", "1"),
+        ("3>=4", "0"),
+        ("1!=2", "1"),
+        ("6&3", "2"),
+        ("6^3", "5"),
+        ("6|3", "7"),
+        ("1|2^3&4", "3"),
+        ("1&&0||1", "1"),
+    ];
+    for (expr, value) in cases {
+        assert_eq!(posix(&format!("eval(`{expr}')")), value, "{expr}");
+    }
+}
+
+// Macros: eval's optional radix and minimum-digits arguments.
+#[test]
+fn eval_radix_and_width() {
+    assert_eq!(posix("eval(255, 16) eval(5, 2, 8)\n"), "ff 00000101\n");
+}
+
+// Macros: m4wrap "shall cause the string to be processed ... at the end of
+// input".
+#[test]
+fn m4wrap_runs_at_end_of_input() {
+    assert_eq!(posix("m4wrap(`wrapped\n')text\n"), "text\nwrapped\n");
+}
+
+// Macros: errprint writes to standard error, not the output.
+#[test]
+fn errprint_writes_nothing_to_output() {
+    assert_eq!(posix("errprint(`oops\n')ok\n"), "ok\n");
+}
+
+// Macros: syscmd and sysval.
+#[cfg(unix)]
+#[test]
+fn syscmd_sets_sysval() {
+    assert_eq!(posix("syscmd(`exit 3')sysval\n"), "3\n");
+}
+
+// OPTIONS: -D name[=val] and -U name, from the EXAMPLES section.
+#[test]
+fn command_line_definitions() {
+    let src = concat!(
+        "The value of `VER' is \"VER\".\n",
+        "ifdef(`VER', ``VER'' is defined to be VER., VER is not defined.)\n",
+        "ifelse(VER, 1, ``VER'' is `VER'.)\n",
+        "ifelse(VER, 2, ``VER'' is `VER'., ``VER'' is not 2.)\n",
+        "end\n",
+    );
+    let cases: [(&[&str], &str); 4] = [
+        (
+            &["-U", "VER"],
+            "The value of VER is \"VER\".\nVER is not defined.\n\nVER is not 2.\nend\n",
+        ),
+        (
+            &["-D", "VER"],
+            "The value of VER is \"\".\nVER is defined to be .\n\nVER is not 2.\nend\n",
+        ),
+        (
+            &["-D", "VER=1"],
+            "The value of VER is \"1\".\nVER is defined to be 1.\nVER is 1.\nVER is not 2.\nend\n",
+        ),
+        (
+            &["-D", "VER=2"],
+            "The value of VER is \"2\".\nVER is defined to be 2.\n\nVER is 2.\nend\n",
+        ),
+    ];
+    for (args, expected) in cases {
+        assert_eq!(posix_cli(args, src), (Some(0), expected.into()), "{args:?}");
+    }
+}
+
+// GNU extensions are not builtins in traditional mode, so their names pass
+// through as ordinary text.
+#[test]
+fn gnu_extensions_are_absent() {
+    assert_eq!(
+        posix("__gnu__ format(`%d', 1) regexp(`a', `a') indir(`divnum')\n"),
+        "__gnu__ format(%d, 1) regexp(a, a) indir(divnum)\n"
+    );
+}
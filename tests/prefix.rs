@@ -0,0 +1,51 @@
+//! `--prefix-builtins`: builtins are only reachable as `m4_name`.
+
+mod common;
+
+use common::m4_with;
+use lc_m4::EngineBuilder;
+
+fn prefixed(input: &str) -> String {
+    m4_with(EngineBuilder::new().prefix_builtins(true), input)
+}
+
+#[test]
+fn builtins_are_only_reachable_with_the_prefix() {
+    assert_eq!(
+        prefixed("define(`x', y)len(abc) m4_len(abc) m4___file__"),
+        "define(x, y)len(abc) 3 stdin"
+    );
+}
+
+#[test]
+fn bare_names_are_free_for_the_input() {
+    assert_eq!(
+        prefixed("m4_define(`define', `mine')define m4_define(`x', X)x"),
+        "mine X"
+    );
+}
+
+#[test]
+fn builtin_takes_the_bare_name() {
+    assert_eq!(
+        prefixed("m4_builtin(`len', abc) m4_builtin(`m4_len', abc)"),
+        "3 "
+    );
+}
+
+#[test]
+fn platform_macros_keep_their_names() {
+    assert_eq!(
+        prefixed("m4_ifdef(`__gnu__', gnu) m4_ifdef(`m4___gnu__', prefixed)"),
+        "gnu "
+    );
+}
+
+#[test]
+fn traditional_mode_prefixes_too() {
+    let builder = EngineBuilder::new().traditional().prefix_builtins(true);
+    assert_eq!(
+        m4_with(builder, "m4_len(ab) len(ab) m4_regexp"),
+        "2 len(ab) m4_regexp"
+    );
+}
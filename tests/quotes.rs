@@ -0,0 +1,82 @@
+//! Quoted strings in the main scan: one level of quotes comes off and the
+//! text inside isn't expanded.
+
+mod common;
+
+use common::{m4, m4_with};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn empty_quotes_separate_names() {
+    assert_eq!(m4("define(`ab', X)a`'b ab"), "ab X");
+}
+
+#[test]
+fn macros_inside_quotes_are_not_expanded() {
+    assert_eq!(m4("define(`x', y)`x (x) `x'' x'"), "x (x) `x' y'");
+}
+
+#[test]
+fn stray_end_quotes_are_plain_text() {
+    assert_eq!(m4("a' b"), "a' b");
+}
+
+#[test]
+fn empty_start_quote_disables_quoting() {
+    assert_eq!(m4("define(`x', y)changequote(,)`x'"), "`y'");
+}
+
+#[test]
+fn equal_quotes_do_not_nest() {
+    assert_eq!(m4("define(`x', y)changequote(|,|)|x|x|x|"), "xyx");
+}
+
+#[test]
+fn multi_character_quotes_nest() {
+    assert_eq!(
+        m4("define(`x', y)changequote(<<,>>)<<x<<x>>x>> x"),
+        "x<<x>>x y"
+    );
+}
+
+#[test]
+fn multi_character_quotes_can_span_an_expansion() {
+    assert_eq!(
+        m4("define(`x', y)define(`lt', `<')changequote(<<,>>)lt<x>>"),
+        "x"
+    );
+}
+
+#[test]
+fn changequote_without_arguments_restores_the_defaults() {
+    assert_eq!(m4("changequote([, ])`a'[b]changequote`c'[d]"), "`a'bc[d]");
+    assert_eq!(m4("changequote(,)`a'changequote`b'"), "`a'b");
+}
+
+#[test]
+fn changequote_with_one_empty_argument_disables_quoting() {
+    assert_eq!(m4("define(`x', y)changequote()`x' x"), "`y' y");
+}
+
+#[test]
+fn missing_or_empty_end_quote_defaults() {
+    assert_eq!(m4("define(`x', y)changequote([)[x'x"), "xy");
+    assert_eq!(m4("define(`x', y)changequote([, `')[x'x"), "xy");
+}
+
+#[test]
+fn new_quotes_apply_to_the_rest_of_the_expansion() {
+    assert_eq!(
+        m4("define(`x', X)define(`q', `changequote([,])[x]x')q[x]`x'"),
+        "xXx`X'"
+    );
+}
+
+#[test]
+fn non_ascii_quotes() {
+    assert_eq!(m4("define(`x', y)changequote(«, »)«x «x»» x"), "x «x» y");
+    assert_eq!(
+        m4_with(EngineBuilder::new().quotes("«", "»"), "define(«x», y)«x» x"),
+        "x y"
+    );
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::{m4, m4_warnings, m4_with};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn regexp_finds_the_first_match() {
+    assert_eq!(m4("regexp(GNUs not Unix,\\<[a-z]\\w+)"), "5");
+    assert_eq!(m4("regexp(GNUs not Unix,\\<Q\\w*)"), "-1");
+}
+
+#[test]
+fn regexp_expands_the_replacement() {
+    assert_eq!(
+        m4("regexp(GNUs not Unix,\\w\\(\\w+\\)$,*** \\& *** \\1 ***)"),
+        "*** Unix *** nix ***"
+    );
+    assert_eq!(m4("regexp(GNUs not Unix,\\<Q\\w*,*** \\& *** \\1 ***)"), "");
+}
+
+#[test]
+fn leftmost_match_is_the_longest() {
+    assert_eq!(m4("regexp(abcd,b\\|bc,\\&)"), "bc");
+    assert_eq!(m4("regexp(aaa,a*,\\&)"), "aaa");
+}
+
+#[test]
+fn anchors_match_at_lines_and_text_ends() {
+    assert_eq!(m4("regexp(a\nb,^b)"), "2");
+    assert_eq!(m4("regexp(a\nb,a$)"), "0");
+    assert_eq!(m4("regexp(a\nb,\\`b)"), "-1");
+}
+
+#[test]
+fn back_references_repeat_a_group() {
+    assert_eq!(m4("regexp(abcbc,\\(bc\\)\\1,\\&)"), "bcbc");
+}
+
+#[test]
+fn missing_group_warns() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "regexp(abc,b,[\\1])");
+    assert_eq!(output, "[]");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: sub-expression 1 not present\n"
+    );
+}
+
+#[test]
+fn bad_regexp_warns() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "regexp(abc,\\(b))");
+    assert_eq!(output, "");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: bad regular expression: `\\(b)': Unmatched ( or \\(\n"
+    );
+}
+
+#[test]
+fn regexp_of_text_alone_is_zero() {
+    assert_eq!(m4("regexp(abc)"), "0");
+}
+
+#[test]
+fn patsubst_replaces_every_match() {
+    assert_eq!(m4("patsubst(GNUs not Unix,not,NOT)"), "GNUs NOT Unix");
+    assert_eq!(
+        m4("patsubst(GNUs not Unix,\\w+,<\\&>)"),
+        "<GNUs> <not> <Unix>"
+    );
+}
+
+#[test]
+fn patsubst_without_replacement_deletes() {
+    assert_eq!(m4("patsubst(a1b22c,[0-9]+)"), "abc");
+}
+
+#[test]
+fn empty_matches_advance_by_one_character() {
+    assert_eq!(m4("patsubst(abc,x*,-)"), "-a-b-c-");
+}
+
+#[test]
+fn anchors_apply_to_each_line() {
+    assert_eq!(m4("patsubst(a\nb\n,^,> )"), "> a\n> b\n> ");
+    assert_eq!(m4("patsubst(a\nb,$,;)"), "a;\nb;");
+}
+
+#[test]
+fn patsubst_uses_groups() {
+    assert_eq!(
+        m4("patsubst(Hello world,\\(\\w+\\) \\(\\w+\\),\\2 \\1)"),
+        "world Hello"
+    );
+}
+
+#[test]
+fn patsubst_of_text_alone_is_the_text() {
+    assert_eq!(m4("patsubst(abc)"), "abc");
+}
+
+#[test]
+fn regexp_and_patsubst_need_gnu_mode() {
+    let builder = EngineBuilder::new().traditional();
+    assert_eq!(m4_with(builder, "regexp patsubst"), "regexp patsubst");
+}
+
+#[test]
+fn failed_search_of_long_text_finishes() {
+    let text = "a".repeat(50_000);
+    assert_eq!(m4(&format!("regexp(`{text}', `ab')")), "-1");
+    assert_eq!(m4(&format!("patsubst(`{text}', `b')")), text);
+}
+
+#[test]
+fn patsubst_of_many_matches_finishes() {
+    let text = "ab".repeat(50_000);
+    assert_eq!(
+        m4(&format!("patsubst(`{text}', `b', `c')")),
+        "ac".repeat(50_000)
+    );
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::{m4, m4_frozen};
+
+#[test]
+fn expansion_is_rescanned() {
+    assert_eq!(m4_frozen("rescan.m4f", "a\n"), "text\n");
+}
+
+#[test]
+fn expansion_is_retokenized() {
+    assert_eq!(m4_frozen("rescan.m4f", "p p\n"), "text text text text\n");
+}
+
+#[test]
+fn empty_expansion_leaves_neighbours_alone() {
+    assert_eq!(m4_frozen("rescan.m4f", "x e y\n"), "x  y\n");
+}
+
+#[test]
+fn comment_opened_in_expansion_continues_in_input() {
+    assert_eq!(m4_frozen("rescan.m4f", "c a\na\n"), "#open a\ntext\n");
+}
+
+#[test]
+fn recursive_macros_terminate() {
+    assert_eq!(
+        m4("define(`f', `ifelse($1, 0, done, `$1 f(decr($1))')')f(3)"),
+        "3 2 1 done"
+    );
+}
+
+#[test]
+fn expansion_can_take_its_arguments_from_the_input() {
+    assert_eq!(m4("define(`g', `len(')g abc)"), "3");
+}
+
+#[test]
+fn builtin_results_are_rescanned() {
+    assert_eq!(m4("define(`x', X)substr(`xyz', 0, 1)"), "X");
+}
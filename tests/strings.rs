@@ -0,0 +1,119 @@
+mod common;
+
+use common::{m4, m4_warnings};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn len_counts_bytes() {
+    assert_eq!(m4("len(hello) len() len(a b)"), "5 0 3");
+    assert_eq!(m4("len(h\u{e9})"), "3");
+}
+
+#[test]
+fn index_finds_the_first_occurrence() {
+    assert_eq!(
+        m4("index(hello,l) index(hello,z) index(hello,lo)"),
+        "2 -1 3"
+    );
+}
+
+#[test]
+fn index_of_the_empty_string_is_zero() {
+    assert_eq!(m4("index(abc,) index(abc) index(,)"), "0 0 0");
+}
+
+#[test]
+fn index_counts_bytes() {
+    assert_eq!(m4("index(h\u{e9}llo,l)"), "3");
+}
+
+#[test]
+fn substr_takes_a_start_and_length() {
+    assert_eq!(m4("substr(hello,1,3) substr(hello,2)"), "ell llo");
+}
+
+#[test]
+fn substr_clips_out_of_range_bounds() {
+    assert_eq!(
+        m4("substr(hello,3,10),substr(hello,5),substr(hello,-1,2),substr(hello,1,0)"),
+        "lo,,,"
+    );
+}
+
+#[test]
+fn substr_warns_about_bad_bounds() {
+    let run = |input| m4_warnings(EngineBuilder::new(), input);
+    let warning = |what| format!("m4:stdin:1: Warning: {what} builtin `substr'\n");
+    assert_eq!(
+        run("substr(hello,x)substr(hello,1x)substr(hello,1,y)"),
+        (String::new(), warning("non-numeric argument to").repeat(3))
+    );
+    assert_eq!(
+        run("substr(hello,)"),
+        ("hello".into(), warning("empty string treated as 0 in"))
+    );
+    assert_eq!(
+        run("substr(hello,1,)"),
+        (String::new(), warning("empty string treated as 0 in"))
+    );
+}
+
+#[test]
+fn substr_bounds_are_c_ints() {
+    let run = |input| m4_warnings(EngineBuilder::new(), input);
+    assert_eq!(
+        run("substr(hello,4294967297)"),
+        ("ello".into(), String::new())
+    );
+    // An overflowing bound saturates to LONG_MAX, which is -1 as an int
+    let overflow = "m4:stdin:1: Warning: numeric overflow detected in builtin `substr'\n";
+    assert_eq!(
+        run("substr(hello,99999999999999999999)"),
+        (String::new(), overflow.into())
+    );
+    assert_eq!(
+        run("substr(hello,2,99999999999999999999)"),
+        (String::new(), overflow.into())
+    );
+    assert_eq!(
+        run("substr(hello,2,2147483647)"),
+        ("llo".into(), String::new())
+    );
+}
+
+#[test]
+fn substr_without_bounds_is_the_whole_text() {
+    assert_eq!(m4("substr(hello)"), "hello");
+}
+
+#[test]
+fn translit_maps_and_deletes() {
+    assert_eq!(m4("translit(abcd,ab,AB) translit(abcd,bd)"), "ABcd ac");
+}
+
+#[test]
+fn translit_expands_ranges() {
+    assert_eq!(m4("translit(hello,a-z,A-Z)"), "HELLO");
+    assert_eq!(m4("translit(abc,a-c,c-a)"), "cba");
+    assert_eq!(m4("translit(a-b,-b,_B)"), "a_B");
+    assert_eq!(m4("translit(a-b,a-,A_)"), "A_b");
+}
+
+#[test]
+fn translit_first_mapping_wins() {
+    assert_eq!(m4("translit(aab,aa,xy)"), "xxb");
+}
+
+#[test]
+fn translit_is_byte_exact() {
+    // The UTF-8 bytes of "é" are translated and measured one at a time
+    assert_eq!(m4("len(translit(h\u{e9},h))"), "2");
+    assert_eq!(m4("len(translit(h\u{e9},\u{c3}))"), "2");
+    assert_eq!(m4("translit(a\u{e9}b,\u{80}-\u{ff})"), "ab");
+    assert_eq!(m4("translit(a\0b\0,\0a,-)"), "-b-");
+}
+
+#[test]
+fn translit_without_sets_is_the_text() {
+    assert_eq!(m4("translit(abc)"), "abc");
+}
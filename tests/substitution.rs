@@ -0,0 +1,62 @@
+//! Argument references in the bodies of text macros.
+
+mod common;
+
+use common::{m4, m4_warnings};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn numbered_references() {
+    assert_eq!(m4("define(`f', ``$0':$2$1$3')f(a, b)"), "f:ba");
+}
+
+#[test]
+fn argument_count() {
+    assert_eq!(m4("define(`n', `$#')n n() n(a, b, c)"), "0 1 3");
+}
+
+#[test]
+fn joined_arguments() {
+    assert_eq!(
+        m4("define(`all', `$* $@')define(`x', X)all(x, `x')"),
+        "X,X X,x"
+    );
+}
+
+#[test]
+fn other_dollars_are_kept() {
+    assert_eq!(m4("define(`d', `$ $a $')d(1)"), "$ $a $");
+}
+
+#[test]
+fn references_past_nine_read_every_digit() {
+    let input = "define(`ten', `$10 ${10} ${1}0')ten(a,b,c,d,e,f,g,h,i,j)";
+    assert_eq!(m4(input), "j j a0");
+}
+
+#[test]
+fn braces_need_a_number() {
+    assert_eq!(m4("define(`b', `${} ${x} ${1')b(a)"), "${} ${x} ${1");
+}
+
+#[test]
+fn many_braced_references_are_cheap() {
+    let body = "${1}".repeat(20_000);
+    assert_eq!(
+        m4(&format!("define(`b', `{body}')b(x)")),
+        "x".repeat(20_000)
+    );
+}
+
+#[test]
+fn traditional_mode_reads_one_digit() {
+    let (output, warnings) = m4_warnings(
+        EngineBuilder::new().traditional(),
+        "define(`ten', `$10 ${1}')ten(a,b,c,d,e,f,g,h,i,j)",
+    );
+    assert_eq!(output, "a0 ${1}");
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: `$10' in the body of `ten' is `$1' followed by `0'\n"
+    );
+}
@@ -0,0 +1,78 @@
+//! `#line` markers for `--synclines`.
+
+mod common;
+
+use std::fs;
+
+use common::{m4_with, temp_file};
+use lc_m4::EngineBuilder;
+
+fn synced(input: &str) -> String {
+    m4_with(EngineBuilder::new().synclines(true), input)
+}
+
+// The example from the GNU manual's "Preprocessor features" section
+#[test]
+fn markers_follow_the_input_lines() {
+    let input = concat!(
+        "define(`twoline', `1\n2')\n",
+        "changecom(`/*', `*/')\n",
+        "define(`comment', `/*1\n2*/')\n",
+        "dnl no line\n",
+        "hello\n",
+        "twoline\n",
+        "comment\n",
+        "one comment `two\nthree'\n",
+        "goodbye\n",
+    );
+    assert_eq!(
+        synced(input),
+        concat!(
+            "#line 2 \"stdin\"\n",
+            "\n",
+            "\n",
+            "#line 5\n",
+            "\n",
+            "#line 7\n",
+            "hello\n",
+            "1\n",
+            "#line 8\n",
+            "2\n",
+            "/*1\n2*/\n",
+            "#line 10\n",
+            "one /*1\n2*/ two\nthree\n",
+            "#line 12\n",
+            "goodbye\n",
+        )
+    );
+}
+
+#[test]
+fn output_in_step_with_the_input_needs_one_marker() {
+    assert_eq!(synced("a\nb\n"), "#line 1 \"stdin\"\na\nb\n");
+}
+
+#[test]
+fn diversions_name_the_file_again() {
+    assert_eq!(
+        synced("a\ndivert(1)b\ndivert`'c\n"),
+        "#line 1 \"stdin\"\na\n#line 3 \"stdin\"\nc\n#line 2 \"stdin\"\nb\n"
+    );
+}
+
+#[test]
+fn includes_name_their_file() {
+    let path = temp_file("synced", "inner\n");
+    let name = path.to_str().unwrap();
+    let output = synced(&format!("include(`{name}')dnl\nouter\n"));
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        output,
+        format!("#line 1 \"{name}\"\ninner\n#line 2 \"stdin\"\nouter\n")
+    );
+}
+
+#[test]
+fn off_by_default() {
+    assert_eq!(m4_with(EngineBuilder::new(), "dnl\na\n"), "a\n");
+}
@@ -0,0 +1,136 @@
+//! Builtins that run shell commands.
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+
+use common::{m4, m4_cli, m4_warnings, m4_with};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn syscmd_output_follows_earlier_output() {
+    assert_eq!(
+        m4_cli(&[], "before\nsyscmd(echo middle)after\n").0,
+        "before\nmiddle\nafter\n"
+    );
+}
+
+#[test]
+fn syscmd_expands_to_nothing() {
+    assert_eq!(m4_cli(&[], "< syscmd(true) >\n").0, "<  >\n");
+}
+
+#[test]
+fn syscmd_is_absent_in_safer_mode() {
+    assert_eq!(
+        m4_with(EngineBuilder::new().safer(true), "syscmd(echo no)"),
+        "syscmd(echo no)"
+    );
+}
+
+#[test]
+fn esyscmd_output_is_rescanned() {
+    assert_eq!(m4("esyscmd(printf divnum)"), "0");
+    assert_eq!(m4("esyscmd(echo hi) there"), "hi\n there");
+}
+
+#[test]
+fn esyscmd_is_gnu_only_and_absent_in_safer_mode() {
+    assert_eq!(
+        m4_with(EngineBuilder::new().traditional(), "esyscmd(echo no)"),
+        "esyscmd(echo no)"
+    );
+    assert_eq!(
+        m4_with(EngineBuilder::new().safer(true), "esyscmd(echo no)"),
+        "esyscmd(echo no)"
+    );
+}
+
+#[test]
+fn sysval_reports_the_last_exit_status() {
+    assert_eq!(m4("sysval"), "0");
+    assert_eq!(
+        m4_cli(&[], "syscmd(exit 3)sysval syscmd(true)sysval\n").0,
+        "3 0\n"
+    );
+    assert_eq!(m4("esyscmd(exit 4)sysval"), "4");
+}
+
+#[test]
+fn sysval_encodes_signals() {
+    assert_eq!(m4("esyscmd(kill -9 $$)sysval"), "2304");
+}
+
+/// The file name in an expansion, whether or not it's still quoted.
+fn unquoted(name: &str) -> &str {
+    name.trim_start_matches('`').trim_end_matches('\'')
+}
+
+#[test]
+fn mkstemp_creates_a_private_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let template = env::temp_dir().join("lc-m4-mkstemp-XXX");
+    let template = template.to_str().unwrap();
+    let out = m4(&format!("mkstemp({template})"));
+    let name = unquoted(&out);
+    let stem = template.trim_end_matches('X');
+    assert!(name.starts_with(stem), "{name}");
+    // Padded to six random characters
+    assert_eq!(name.len(), stem.len() + 6, "{name}");
+    let mode = fs::metadata(name).unwrap().permissions().mode();
+    fs::remove_file(name).unwrap();
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[test]
+fn mkstemp_failure_is_an_error() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new(), "mkstemp(/nonexistent/fileXXXXXX)");
+    assert_eq!(output, "");
+    assert!(
+        warnings.starts_with(
+            "m4:stdin:1: cannot create file from template `/nonexistent/fileXXXXXX': "
+        ),
+        "{warnings}"
+    );
+}
+
+#[test]
+fn maketemp_warns_in_gnu_mode() {
+    let template = env::temp_dir().join("lc-m4-maketemp-XXXXXX");
+    let (output, warnings) = m4_warnings(
+        EngineBuilder::new(),
+        &format!("maketemp({})", template.to_str().unwrap()),
+    );
+    fs::remove_file(unquoted(&output)).unwrap();
+    assert_eq!(
+        warnings,
+        "m4:stdin:1: Warning: recommend using mkstemp instead\n"
+    );
+}
+
+#[test]
+fn maketemp_uses_the_pid_in_traditional_mode() {
+    let (out, _, _) = m4_cli(
+        &["--traditional"],
+        "maketemp(fooXXXXXXXXXXXX) maketemp(aX) maketemp(X)\n",
+    );
+    let pid = out.split(' ').next().unwrap().trim_start_matches("foo");
+    assert_eq!(pid.len(), 12, "{out}");
+    let pid: u32 = pid.parse().unwrap();
+    let last = (pid % 10).to_string();
+    assert_eq!(out, format!("foo{pid:012} a{last} X\n"));
+}
+
+#[test]
+fn temp_files_are_absent_in_safer_mode() {
+    assert_eq!(
+        m4_with(
+            EngineBuilder::new().safer(true),
+            "mkstemp(xXXXXXX) maketemp(xXXXXXX)"
+        ),
+        "mkstemp(xXXXXXX) maketemp(xXXXXXX)"
+    );
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use std::fs;
+
+use common::{m4_with, temp_file};
+use lc_m4::EngineBuilder;
+
+/// Runs `input` and returns what was written to the debug output.
+fn traced(builder: EngineBuilder<'_>, input: &str) -> String {
+    let mut debug = Vec::new();
+    m4_with(builder.debug_output(&mut debug), input);
+    String::from_utf8(debug).unwrap()
+}
+
+#[test]
+fn trace_option_traces_calls() {
+    assert_eq!(
+        traced(
+            EngineBuilder::new().trace("foo"),
+            "define(foo,bar)foo(1,2) foo"
+        ),
+        "m4trace: -1- foo(`1', `2') -> `bar'\nm4trace: -1- foo -> `bar'\n"
+    );
+}
+
+#[test]
+fn trace_shows_builtin_expansions() {
+    assert_eq!(
+        traced(
+            EngineBuilder::new().trace("len").trace("dnl"),
+            "len(abc) dnl\n"
+        ),
+        "m4trace: -1- len(`abc') -> `3'\nm4trace: -1- dnl -> `'\n"
+    );
+}
+
+#[test]
+fn include_expands_to_nothing() {
+    let path = temp_file("traced-include", "included\n");
+    let input = format!("traceon(`include')include(`{}')", path.display());
+    let trace = traced(EngineBuilder::new().debug_flags("eq"), &input);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(trace, "m4trace: -1- include -> `'\n");
+}
+
+#[test]
+fn traceon_names_macros_before_they_are_defined() {
+    assert_eq!(
+        traced(EngineBuilder::new(), "traceon(foo)define(foo,bar)foo"),
+        "m4trace: -1- foo -> `bar'\n"
+    );
+}
+
+#[test]
+fn traceon_without_arguments_traces_everything_defined() {
+    assert_eq!(
+        traced(EngineBuilder::new(), "define(foo,x)traceon len(foo)"),
+        "m4trace: -2- foo -> `x'\nm4trace: -1- len(`x') -> `1'\n"
+    );
+}
+
+#[test]
+fn traceoff_stops_tracing() {
+    assert_eq!(
+        traced(
+            EngineBuilder::new(),
+            "traceon(foo)define(foo,x)foo traceoff foo"
+        ),
+        "m4trace: -1- foo -> `x'\n"
+    );
+}
+
+#[test]
+fn debug_flags_shape_the_trace_line() {
+    assert_eq!(
+        traced(
+            EngineBuilder::new().trace("len").debug_flags("ae"),
+            "len(abc)"
+        ),
+        "m4trace: -1- len(abc) -> 3\n"
+    );
+    assert_eq!(
+        traced(
+            EngineBuilder::new().trace("len").debug_flags(""),
+            "len(abc)"
+        ),
+        "m4trace: -1- len\n"
+    );
+    assert_eq!(
+        traced(
+            EngineBuilder::new().trace("len").debug_flags("fl"),
+            "\nlen(abc)"
+        ),
+        "m4trace:stdin:2: -1- len\n"
+    );
+}
+
+#[test]
+fn calls_are_placed_where_their_name_was_read() {
+    assert_eq!(
+        traced(
+            EngineBuilder::new().trace("len").debug_flags("l"),
+            "\nlen(a\n\nb)"
+        ),
+        "m4trace:2: -1- len\n"
+    );
+}
+
+#[test]
+fn t_flag_traces_every_call() {
+    assert_eq!(
+        traced(EngineBuilder::new().debug_flags("t"), "len(abc) incr(1)"),
+        "m4trace: -1- len\nm4trace: -1- incr\n"
+    );
+}
+
+#[test]
+fn debugmode_replaces_the_flags() {
+    assert_eq!(
+        traced(EngineBuilder::new().trace("len"), "debugmode(e)len(abc)"),
+        "m4trace: -1- len -> 3\n"
+    );
+}
+
+#[test]
+fn debugmode_adds_and_removes_flags() {
+    assert_eq!(
+        traced(
+            EngineBuilder::new().trace("len"),
+            "debugmode(-q)len(abc) debugmode(+fq)len(abc)"
+        ),
+        "m4trace: -1- len(abc) -> 3\nm4trace:stdin: -1- len(`abc') -> `3'\n"
+    );
+}
+
+#[test]
+fn debugmode_without_arguments_clears_and_empty_restores() {
+    assert_eq!(
+        traced(
+            EngineBuilder::new().trace("len"),
+            "debugmode len(a) debugmode()len(a)"
+        ),
+        "m4trace: -1- len\nm4trace: -1- len(`a') -> `1'\n"
+    );
+}
+
+#[test]
+fn debugmode_v_sets_everything() {
+    assert_eq!(
+        traced(EngineBuilder::new().debug_flags(""), "debugmode(V)len(a)"),
+        "m4trace:stdin:1: -1- len(`a') -> `1'\n"
+    );
+}
+
+#[test]
+fn debugmode_rejects_unknown_flags() {
+    let mut warnings = Vec::new();
+    let debug = traced(
+        EngineBuilder::new()
+            .trace("len")
+            .diagnostic_output(&mut warnings),
+        "debugmode(+z)len(a)",
+    );
+    assert_eq!(debug, "m4trace: -1- len(`a') -> `1'\n");
+    assert_eq!(warnings, b"m4:stdin:1: Warning: bad debug flags: `+z'\n");
+}
+
+#[test]
+fn debugfile_appends_trace_output_to_a_file() {
+    let path = temp_file("debugfile", "earlier\n");
+    let name = path.to_str().unwrap();
+    let debug = traced(
+        EngineBuilder::new().trace("len"),
+        &format!("len(a) debugfile(`{name}')len(ab) debugfile len(abc)"),
+    );
+    let file = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(debug, "m4trace: -1- len(`a') -> `1'\n");
+    assert_eq!(file, "earlier\nm4trace: -1- len(`ab') -> `2'\n");
+}
+
+#[test]
+fn debugfile_keeps_the_old_output_if_the_file_cannot_be_opened() {
+    let mut warnings = Vec::new();
+    let debug = traced(
+        EngineBuilder::new()
+            .trace("len")
+            .diagnostic_output(&mut warnings),
+        "debugfile(/nonexistent/debug)len(a)",
+    );
+    assert_eq!(debug, "m4trace: -1- len(`a') -> `1'\n");
+    let warnings = String::from_utf8(warnings).unwrap();
+    assert!(
+        warnings.starts_with("m4:stdin:1: Warning: cannot set debug file `/nonexistent/debug': "),
+        "{warnings}"
+    );
+}
@@ -0,0 +1,46 @@
+//! `--quiet` and `--fatal-warning`: which diagnostics are written, and
+//! which of them fail the run.
+
+mod common;
+
+use common::{m4_cli, m4_warnings};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn quiet_leaves_out_warnings() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new().quiet(true), "len(a, b)incr() dnl");
+    assert_eq!(output, "11 ");
+    assert_eq!(warnings, "");
+}
+
+#[test]
+fn quiet_still_reports_errors() {
+    let (output, warnings) = m4_warnings(EngineBuilder::new().quiet(true), "`open");
+    assert_eq!(output, "open");
+    assert_eq!(warnings, "m4:stdin:1: end of file in string\n");
+}
+
+#[test]
+fn errprint_is_not_a_warning() {
+    let (_, warnings) = m4_warnings(EngineBuilder::new().quiet(true), "errprint(`said')");
+    assert_eq!(warnings, "said");
+}
+
+#[test]
+fn warnings_only_fail_the_run_when_fatal() {
+    let (_, warnings, status) = m4_cli(&[], "len(a, b)");
+    assert_eq!(status, Some(0));
+    assert!(warnings.contains("excess arguments"), "{warnings}");
+    let (_, warnings, status) = m4_cli(&["-E"], "len(a, b)");
+    assert_eq!(status, Some(1));
+    assert!(warnings.contains("excess arguments"), "{warnings}");
+}
+
+#[test]
+fn quiet_warnings_are_not_fatal() {
+    let (_, warnings, status) = m4_cli(&["-QE"], "len(a, b)");
+    assert_eq!((status, warnings.as_str()), (Some(0), ""));
+    let (_, warnings, status) = m4_cli(&["--quiet", "--fatal-warning"], "`open");
+    assert_eq!(status, Some(1));
+    assert!(warnings.contains("end of file in string"), "{warnings}");
+}
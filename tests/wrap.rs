@@ -0,0 +1,69 @@
+mod common;
+
+use std::fs;
+
+use common::{m4, m4_cli, m4_with, temp_file};
+use lc_m4::EngineBuilder;
+
+#[test]
+fn wrapped_text_follows_the_input() {
+    assert_eq!(m4("m4wrap(wrapped)text\n"), "text\nwrapped");
+}
+
+#[test]
+fn gnu_mode_rescans_the_last_wrap_first() {
+    assert_eq!(m4("m4wrap(a)m4wrap(b)m4wrap(divnum)"), "0ba");
+}
+
+#[test]
+fn traditional_mode_rescans_wraps_in_order() {
+    assert_eq!(
+        m4_with(
+            EngineBuilder::new().traditional(),
+            "m4wrap(a)m4wrap(b)m4wrap(divnum)"
+        ),
+        "ab0"
+    );
+}
+
+// Each call is saved in two pieces, so it isn't made until the pieces are
+// reread together at the end of input.
+#[test]
+fn wraps_made_while_rescanning_form_a_later_round() {
+    assert_eq!(m4("m4wrap(a)m4wrap(wrap(c))m4wrap(m4)m4wrap(b )"), "b ac");
+    assert_eq!(
+        m4_with(
+            EngineBuilder::new().traditional(),
+            "m4wrap(a )m4wrap(m4)m4wrap(wrap(c))m4wrap(b)"
+        ),
+        "a bc"
+    );
+}
+
+// From the GNU manual: "If m4wrap is not used recursively, the saved pieces
+// of text are reread in the opposite order in which they were saved".
+#[test]
+fn gnu_manual_ordering() {
+    assert_eq!(m4("m4wrap(`a`'m4wrap(`c\n', `d')')m4wrap(`b')"), "bac\n d");
+}
+
+#[test]
+fn gnu_mode_joins_arguments_with_spaces() {
+    assert_eq!(m4("m4wrap(a,b)"), "a b");
+    assert_eq!(
+        m4_with(EngineBuilder::new().traditional(), "m4wrap(a,b)"),
+        "a"
+    );
+}
+
+// Wrapped text waits for the last input file, so it sees what later files
+// define, and is rescanned before the diversions are written out.
+#[test]
+fn wrapped_text_runs_after_every_input_file() {
+    let first = temp_file("wrap-first", "m4wrap(`late x\n')divert(1)diverted\ndivert");
+    let last = temp_file("wrap-last", "define(`x', `defined')last\n");
+    let (output, _, _) = m4_cli(&[first.to_str().unwrap(), last.to_str().unwrap()], "");
+    fs::remove_file(first).unwrap();
+    fs::remove_file(last).unwrap();
+    assert_eq!(output, "last\nlate defined\ndiverted\n");
+}
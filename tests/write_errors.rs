@@ -0,0 +1,112 @@
+mod common;
+
+use std::cell::Cell;
+use std::io::{self, Write};
+
+use lc_m4::{EngineBuilder, Error, Stream};
+
+/// Accepts `remaining` bytes, then fails every write, counting the attempts.
+struct FailAfter<'a> {
+    remaining: usize,
+    attempts: &'a Cell<usize>,
+}
+
+impl Write for FailAfter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.attempts.set(self.attempts.get() + 1);
+        if self.remaining == 0 {
+            return Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"));
+        }
+        let n = buf.len().min(self.remaining);
+        self.remaining -= n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn sink_failure_is_reported_once() {
+    let attempts = Cell::new(0);
+    let mut engine = EngineBuilder::new()
+        .output(FailAfter {
+            remaining: 10,
+            attempts: &attempts,
+        })
+        .build()
+        .unwrap();
+    let err = engine
+        .process(&mut "lots of words to write out\n".as_bytes())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Write {
+            stream: Stream::Output,
+            ..
+        }
+    ));
+    let after_failure = attempts.get();
+    engine.process(&mut "more words\n".as_bytes()).unwrap();
+    engine.finish().unwrap();
+    assert_eq!(attempts.get(), after_failure);
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::fs::File;
+    use std::io::Write;
+    use std::process::Stdio;
+    use std::thread;
+
+    use crate::common::m4_command;
+
+    fn run_into_closed_pipe(args: &[&str]) -> (Option<i32>, String) {
+        let mut child = m4_command(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        drop(child.stdout.take());
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = thread::spawn(move || {
+            let _ = stdin.write_all("some text\n".repeat(100_000).as_bytes());
+        });
+        let output = child.wait_with_output().unwrap();
+        writer.join().unwrap();
+        (
+            output.status.code(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    }
+
+    #[test]
+    fn closed_pipe_exits_quietly() {
+        let (code, stderr) = run_into_closed_pipe(&[]);
+        assert_eq!(code, Some(0));
+        assert_eq!(stderr, "");
+    }
+
+    #[test]
+    fn closed_pipe_is_an_error_with_fatal_warnings() {
+        let (code, stderr) = run_into_closed_pipe(&["--fatal-warning"]);
+        assert_eq!(code, Some(74));
+        assert!(stderr.contains("write error on stdout"), "{stderr}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn full_disk_reports_the_sink() {
+        let output = m4_command(&[])
+            .stdin(File::open(file!()).unwrap())
+            .stdout(File::create("/dev/full").unwrap())
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(74));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(stderr.matches("write error").count(), 1, "{stderr}");
+        assert!(stderr.contains("write error on stdout"), "{stderr}");
+    }
+}